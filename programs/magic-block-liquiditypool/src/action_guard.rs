@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+
+use ephemeral_rollups_sdk::cpi::DELEGATION_PROGRAM_ID;
+
+use crate::error::ErrorCode;
+
+// Escrow index every commit handler in this program is scheduled with
+pub const ACTION_ESCROW_INDEX: u8 = 0;
+
+/// Checks that a base-layer handler is being run by the delegation program on behalf of
+/// `expected_authority`. The delegation program appends `[escrow, escrow_authority]`, in that
+/// order, to the handler accounts (hence `escrow` before `escrow_auth` in every handler struct)
+/// and signs for the escrow PDA, which nobody else can do.
+pub fn verify_action_escrow(
+    escrow: &AccountInfo,
+    escrow_auth: &AccountInfo,
+    expected_authority: &Pubkey,
+) -> Result<()> {
+    require!(escrow.is_signer, ErrorCode::Unauthorized);
+    require!(escrow_auth.key == expected_authority, ErrorCode::Unauthorized);
+
+    let (expected_escrow, _) = Pubkey::find_program_address(
+        &[b"balance", escrow_auth.key.as_ref(), &[ACTION_ESCROW_INDEX]],
+        &DELEGATION_PROGRAM_ID,
+    );
+    require!(escrow.key() == expected_escrow, ErrorCode::Unauthorized);
+
+    Ok(())
+}
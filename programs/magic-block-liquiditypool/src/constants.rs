@@ -2,3 +2,24 @@ use anchor_lang::prelude::*;
 
 #[constant]
 pub const SEED: &str = "anchor";
+
+#[constant]
+pub const BPS_DENOMINATOR: u128 = 10_000;
+
+#[constant]
+pub const POOLS_PER_REGISTRY_PAGE: usize = 32;
+
+#[constant]
+pub const MAX_TRADE_FEE_BPS: u16 = 1_000;
+
+#[constant]
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 500;
+
+// How long the ER has to apply a deposit or withdraw receipt before it can only be cancelled
+#[constant]
+pub const RECEIPT_TTL_SECONDS: i64 = 15 * 60;
+
+// LP minted to the pool's own `lp_token_account` on the first deposit and never redeemable,
+// so the share price can't be inflated by donating to a near-empty pool
+#[constant]
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
@@ -0,0 +1,11 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ErrorCode;
+
+/// Rejects a user instruction submitted after its `valid_until` unix timestamp,
+/// and returns the current timestamp so callers don't read the clock twice.
+pub fn require_valid_until(valid_until: i64) -> Result<i64> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(now <= valid_until, ErrorCode::DeadlineExceeded);
+    Ok(now)
+}
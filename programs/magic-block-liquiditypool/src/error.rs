@@ -18,4 +18,15 @@ pub enum ErrorCode {
     InsufficientLpTokens,
     ProviderNotFound,
     InsufficientLiquidity,
+    DeadlineExceeded,
+    InvalidMintOrder,
+    InvalidFeeConfig,
+    Unauthorized,
+    InvalidReceipt,
+    ReceiptExpired,
+    InvalidReceiptStatus,
+    InvalidPosition,
+    PositionNotEmpty,
+    ReceiptNotExpired,
+    IncompleteReceipts,
 }
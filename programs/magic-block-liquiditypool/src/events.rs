@@ -0,0 +1,141 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct PoolPaused {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolResumed {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolDeactivated {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolFeesUpdated {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub old_trade_fee_bps: u16,
+    pub new_trade_fee_bps: u16,
+    pub old_protocol_fee_bps: u16,
+    pub new_protocol_fee_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeRecipientUpdated {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub old_fee_recipient: Pubkey,
+    pub new_fee_recipient: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposed {
+    pub pool: Pubkey,
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminAccepted {
+    pub pool: Pubkey,
+    pub old_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolFeesClaimed {
+    pub pool: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolReconciled {
+    pub pool: Pubkey,
+    pub vault_a: u64,
+    pub vault_b: u64,
+    pub lp_supply: u64,
+    // Actual minus expected; positive means the vault holds more than the pool accounts for
+    pub discrepancy_a: i128,
+    pub discrepancy_b: i128,
+    pub lp_discrepancy: i128,
+    pub is_consistent: bool,
+    // A shortfall pauses the pool; a surplus alone doesn't
+    pub is_short: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositReceiptCreated {
+    pub pool: Pubkey,
+    pub provider: Pubkey,
+    pub receipt_id: u64,
+    pub requested_a: u64,
+    pub requested_b: u64,
+    // Actually transferred into the vaults
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub lp_tokens_estimate: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositApplied {
+    pub pool: Pubkey,
+    pub provider: Pubkey,
+    pub receipt_id: u64,
+    pub amount_a_used: u64,
+    pub amount_b_used: u64,
+    pub refund_a: u64,
+    pub refund_b: u64,
+    pub lp_tokens: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositReceiptCancelled {
+    pub pool: Pubkey,
+    pub provider: Pubkey,
+    pub receipt_id: u64,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawReceiptCancelled {
+    pub pool: Pubkey,
+    pub provider: Pubkey,
+    pub receipt_id: u64,
+    pub lp_tokens: u64,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SwapReceiptCancelled {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub receipt_id: u64,
+    pub a_to_b: bool,
+    pub amount: u64,
+    pub timestamp: i64,
+}
@@ -2,11 +2,16 @@
 
 use anchor_lang::prelude::*;
 
-use crate::state::liquidity_provider::{LiquidityPoolInfo, LiquidityProvider};
+use crate::state::liquidity_provider::LiquidityProvider;
+use crate::state::lp_position::LpPosition;
 use crate::state::pool::Pool;
 use crate::error::ErrorCode;
+use crate::math::curve::{quote_deposit, quote_zap_deposit};
+use crate::instructions::swap::apply_swap_to_reserves;
+use crate::state::receipt::ReceiptStatus;
+use crate::events::DepositApplied;
 
-use crate::add_liquidity_on_chain::DepositRecept;
+use crate::add_liquidity_on_chain::{DepositRecept, DepositMode, single_sided_input};
 
 #[derive(Accounts)]
 pub struct AddLiquidityER<'info> {
@@ -20,6 +25,13 @@ pub struct AddLiquidityER<'info> {
     )]
     pub liquidity_provider: Account<'info, LiquidityProvider>,
 
+    #[account(
+        mut,
+        seeds = [b"lp_position", pool.key().as_ref(), provider.key().as_ref()],
+        bump = lp_position.bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+
     #[account(
         mut,
         seeds = [b"pool", pool.name.as_bytes()],
@@ -29,8 +41,15 @@ pub struct AddLiquidityER<'info> {
 
     #[account(
         mut,
-        seeds = [b"deposit_recept", provider.key().as_ref()],
-        bump,
+        seeds = [
+            b"deposit_recept",
+            pool.key().as_ref(),
+            provider.key().as_ref(),
+            deposit_receipt.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = deposit_receipt.bump,
+        constraint = deposit_receipt.pool == pool.key() @ ErrorCode::InvalidReceipt,
+        constraint = deposit_receipt.liquidity_provider == provider.key() @ ErrorCode::InvalidReceipt
     )]
     pub deposit_receipt: Account<'info, DepositRecept>,
 
@@ -46,6 +65,12 @@ pub fn add_liquidity_er(ctx: Context<AddLiquidityER>) -> Result<()> {
     require!(pool.status.is_active, ErrorCode::PoolNotActive);
     require!(!pool.status.is_paused, ErrorCode::PoolPaused);
 
+    require!(deposit_receipt.status == ReceiptStatus::Delegated, ErrorCode::InvalidReceiptStatus);
+    require!(
+        Clock::get()?.unix_timestamp <= deposit_receipt.expires_at,
+        ErrorCode::ReceiptExpired
+    );
+
     msg!("Adding liquidity in ER (state only)...");
 
     **pool.to_account_info().try_borrow_mut_lamports()? -= 1_000_000;
@@ -53,57 +78,91 @@ pub fn add_liquidity_er(ctx: Context<AddLiquidityER>) -> Result<()> {
 
     msg!("Transferred 1,000,000 lamports from pool to deposit_receipt");
 
-    let reserve_a = pool.reserve_a;
-    let reserve_b = pool.reserve_b;
-    let total_lp_supply = pool.total_lp_supply;
-
-    let lp_tokens_to_mint = if total_lp_supply == 0 {
-        let product = (ctx.accounts.deposit_receipt.amount_a as u128)
-            .checked_mul(ctx.accounts.deposit_receipt.amount_b as u128)
-            .ok_or(ErrorCode::MathOverflow)?;
-        (product as f64).sqrt() as u64
-    } else {
-        require!(reserve_a > 0 && reserve_b > 0, ErrorCode::InsufficientReserves);
-        
-        let share_a = (ctx.accounts.deposit_receipt.amount_a as u128)
-            .checked_mul(total_lp_supply as u128)
-            .ok_or(ErrorCode::MathOverflow)?
-            .checked_div(reserve_a as u128)
-            .ok_or(ErrorCode::MathOverflow)? as u64;
-            
-        let share_b = (ctx.accounts.deposit_receipt.amount_b as u128)
-            .checked_mul(total_lp_supply as u128)
-            .ok_or(ErrorCode::MathOverflow)?
-            .checked_div(reserve_b as u128)
-            .ok_or(ErrorCode::MathOverflow)? as u64;
-        
-        std::cmp::min(share_a, share_b)
+    let amount_a = ctx.accounts.deposit_receipt.amount_a;
+    let amount_b = ctx.accounts.deposit_receipt.amount_b;
+
+    let (quote, refund_a, refund_b) = match ctx.accounts.deposit_receipt.mode {
+        DepositMode::Balanced => {
+            // The reserves may have moved since the base layer matched the amounts to the pool ratio
+            let quote = quote_deposit(pool.reserve_a, pool.reserve_b, pool.total_lp_supply, amount_a, amount_b)?;
+            (quote, amount_a - quote.amount_a, amount_b - quote.amount_b)
+        }
+        DepositMode::SingleSided => {
+            let (a_to_b, amount_in) = single_sided_input(amount_a, amount_b)?;
+            let zap = quote_zap_deposit(
+                pool.reserve_a,
+                pool.reserve_b,
+                pool.total_lp_supply,
+                &pool.fees,
+                a_to_b,
+                amount_in,
+            )?;
+
+            // The internal swap moves the reserves and accrues protocol fees like any other swap
+            apply_swap_to_reserves(pool, a_to_b, &zap.swap)?;
+
+            msg!("Zap swapped {} in for {} out (fee {})", zap.swap.amount_in, zap.swap.amount_out, zap.swap.fee);
+
+            // Whatever the deposit leaves of the remainder and the swap output is refunded too
+            let (available_a, available_b) = if a_to_b {
+                (amount_in - zap.swap.amount_in, zap.swap.amount_out)
+            } else {
+                (zap.swap.amount_out, amount_in - zap.swap.amount_in)
+            };
+
+            (zap.deposit, available_a - zap.deposit.amount_a, available_b - zap.deposit.amount_b)
+        }
     };
+    let lp_tokens_to_mint = quote.lp_tokens;
+    let locked_liquidity = quote.locked_liquidity;
+
+    require!(lp_tokens_to_mint >= ctx.accounts.deposit_receipt.min_lp_tokens, ErrorCode::SlippageExceeded);
 
-    require!(lp_tokens_to_mint >= ctx.accounts.deposit_receipt.lp_tokens_minted, ErrorCode::SlippageExceeded);
+    let (deposited_a, deposited_b) = (quote.amount_a, quote.amount_b);
 
     pool.reserve_a = pool.reserve_a
-        .checked_add(ctx.accounts.deposit_receipt.amount_a)
+        .checked_add(deposited_a)
         .ok_or(ErrorCode::MathOverflow)?;
     
     pool.reserve_b = pool.reserve_b
-        .checked_add(ctx.accounts.deposit_receipt.amount_b)
+        .checked_add(deposited_b)
         .ok_or(ErrorCode::MathOverflow)?;
     
     pool.total_lp_supply = pool.total_lp_supply
         .checked_add(lp_tokens_to_mint)
+        .and_then(|supply| supply.checked_add(locked_liquidity))
         .ok_or(ErrorCode::MathOverflow)?;
 
     update_liquidity_provider_add(
         liquidity_provider,
-        pool.key(),
-        pool.lp_mint,
-        ctx.accounts.deposit_receipt.amount_a + ctx.accounts.deposit_receipt.amount_b,
+        &mut ctx.accounts.lp_position,
+        deposited_a + deposited_b,
         lp_tokens_to_mint,
     )?;
 
+    // The base layer mints exactly what the ER credited
+    ctx.accounts.deposit_receipt.lp_tokens_minted = lp_tokens_to_mint;
+    ctx.accounts.deposit_receipt.locked_liquidity = locked_liquidity;
+    ctx.accounts.deposit_receipt.amount_a_used = deposited_a;
+    ctx.accounts.deposit_receipt.amount_b_used = deposited_b;
+    ctx.accounts.deposit_receipt.refund_a = refund_a;
+    ctx.accounts.deposit_receipt.refund_b = refund_b;
+    ctx.accounts.deposit_receipt.status = ReceiptStatus::AppliedInER;
+
     pool.updated_at = Clock::get()?.unix_timestamp;
 
+    emit!(DepositApplied {
+        pool: pool.key(),
+        provider: ctx.accounts.deposit_receipt.liquidity_provider,
+        receipt_id: ctx.accounts.deposit_receipt.receipt_id,
+        amount_a_used: deposited_a,
+        amount_b_used: deposited_b,
+        refund_a,
+        refund_b,
+        lp_tokens: lp_tokens_to_mint,
+        timestamp: pool.updated_at,
+    });
+
     msg!("Liquidity added in ER: {} LP tokens", lp_tokens_to_mint);
     msg!("New reserves: A={}, B={}", pool.reserve_a, pool.reserve_b);
 
@@ -113,37 +172,21 @@ pub fn add_liquidity_er(ctx: Context<AddLiquidityER>) -> Result<()> {
 
 fn update_liquidity_provider_add(
     provider: &mut LiquidityProvider,
-    pool_key: Pubkey,
-    lp_mint: Pubkey,
+    position: &mut LpPosition,
     liquidity_amount: u64,
     lp_tokens: u64,
 ) -> Result<()> {
-    let mut found_index: Option<usize> = None;
-    let mut empty_index: Option<usize> = None;
+    let now = Clock::get()?.unix_timestamp;
 
-    for (i, pool_info) in provider.liquidity_pools_info.iter().enumerate() {
-        if pool_info.pool == pool_key {
-            found_index = Some(i);
-            break;
-        }
-        if empty_index.is_none() && pool_info.pool == Pubkey::default() {
-            empty_index = Some(i);
-        }
-    }
-
-    let index = found_index.or(empty_index)
-        .ok_or(ErrorCode::MaxPoolsReached)?;
-
-    provider.liquidity_pools_info[index] = LiquidityPoolInfo {
-        pool: pool_key,
-        token_mint: lp_mint,
-        liquidity_amount: provider.liquidity_pools_info[index].liquidity_amount
-            .checked_add(liquidity_amount)
-            .ok_or(ErrorCode::MathOverflow)?,
-        lp_tokens: provider.liquidity_pools_info[index].lp_tokens
-            .checked_add(lp_tokens)
-            .ok_or(ErrorCode::MathOverflow)?,
-    };
+    position.liquidity_amount = position.liquidity_amount
+        .checked_add(liquidity_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    position.lp_tokens = position.lp_tokens
+        .checked_add(lp_tokens)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    position.last_updated_at = now;
 
     provider.total_liquidity_provided = provider.total_liquidity_provided
         .checked_add(liquidity_amount)
@@ -153,7 +196,7 @@ fn update_liquidity_provider_add(
         .checked_add(lp_tokens)
         .ok_or(ErrorCode::MathOverflow)?;
 
-    provider.latest_liquidity_provided_on = Clock::get()?.unix_timestamp;
+    provider.latest_liquidity_provided_on = now;
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token::{Mint, Token, TokenAccount, Transfer};
 
 use ephemeral_rollups_sdk::anchor::{commit, delegate};
@@ -9,12 +10,22 @@ use anchor_lang::Discriminator;
 
 
 use crate::error::ErrorCode;
+use crate::math::curve::{quote_deposit, quote_zap_deposit};
+use crate::state::pool::Pool;
+use crate::constants::RECEIPT_TTL_SECONDS;
+use crate::deadline::require_valid_until;
+use crate::state::receipt::{ReceiptCounter, ReceiptStatus};
+use crate::events::DepositReceiptCreated;
 
 #[derive(Accounts)]
+#[instruction(params: DepositLiquidityParams)]
 pub struct DepositLiquidityOnchain<'info> {
     #[account(mut)]
     pub provider: Signer<'info>,
 
+    /// CHECK: Loaded with `Pool::load_checked`; the pool may be delegated to the ER
+    pub pool: UncheckedAccount<'info>,
+
     pub mint_a: Account<'info, Mint>,
     pub mint_b: Account<'info, Mint>,
 
@@ -29,14 +40,14 @@ pub struct DepositLiquidityOnchain<'info> {
         mint::authority = transfer_authority,
         mint::freeze_authority = transfer_authority,
         mint::decimals = 6,
-        seeds = [b"lp_token_mint"],
+        seeds = [b"lp_token_mint", pool.key().as_ref()],
         bump
     )]
     pub lp_mint: Account<'info, Mint>,
 
     #[account(
         mut,
-        seeds = [b"token_account_a", mint_a.key().as_ref()],
+        seeds = [b"token_account_a", pool.key().as_ref()],
         bump,
         token::mint = mint_a,
         token::authority = transfer_authority
@@ -45,22 +56,25 @@ pub struct DepositLiquidityOnchain<'info> {
 
     #[account(
         mut,
-        seeds = [b"token_account_b", mint_b.key().as_ref()],
+        seeds = [b"token_account_b", pool.key().as_ref()],
         bump,
         token::mint = mint_b,
         token::authority = transfer_authority
     )]
     pub token_vault_b: Account<'info, TokenAccount>,
 
+    // Created if missing: a single-sided deposit can be refunded in the token the provider doesn't hold
     #[account(
-        mut,
+        init_if_needed,
+        payer = provider,
         associated_token::mint = mint_a,
         associated_token::authority = provider
     )]
     pub provider_token_a_ata: Account<'info, TokenAccount>,
 
     #[account(
-        mut,
+        init_if_needed,
+        payer = provider,
         associated_token::mint = mint_b,
         associated_token::authority = provider
     )]
@@ -77,111 +91,210 @@ pub struct DepositLiquidityOnchain<'info> {
         init,
         payer = provider,
         space = 8 + DepositRecept::INIT_SPACE,
-        seeds = [b"deposit_recept", provider.key().as_ref()],
+        seeds = [b"deposit_recept", pool.key().as_ref(), provider.key().as_ref(), params.receipt_id.to_le_bytes().as_ref()],
         bump
     )]
     pub deposit_recept: Account<'info, DepositRecept>,
 
+    #[account(
+        init_if_needed,
+        payer = provider,
+        space = 8 + ReceiptCounter::INIT_SPACE,
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump
+    )]
+    pub receipt_counter: Account<'info, ReceiptCounter>,
+
     pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum DepositMode {
+    // Both tokens; LP is minted for the smaller of the two shares
+    Balanced,
+    // Only `amount_a` or only `amount_b`; the ER swaps part of it for the other token at the curve price
+    SingleSided,
+}
+
 #[derive(Clone, AnchorDeserialize, AnchorSerialize)]
 pub struct DepositLiquidityParams {
+    pub mode: DepositMode,
+    // Maximum amounts; a balanced deposit only takes what matches the pool ratio
     pub amount_a: u64,
     pub amount_b: u64,
     pub min_lp_tokens: u64,
-    pub pool: Pubkey,
+    // Client-chosen, unique per pool and provider, so several deposits can be in flight at once
+    pub receipt_id: u64,
+    // Unix timestamp after which neither this instruction nor the ER will accept the deposit
+    pub valid_until: i64,
 }
 
 pub fn deposit_liquidity_on_chain(ctx: Context<DepositLiquidityOnchain>, params: DepositLiquidityParams) -> Result<()> {
 
-    require!(params.amount_a > 0, ErrorCode::InvalidAmount);
-    require!(params.amount_b > 0, ErrorCode::InvalidAmount);
+    let pool = Pool::load_checked(&ctx.accounts.pool)?;
+    pool.require_tradable()?;
+    pool.require_token_accounts(
+        &ctx.accounts.mint_a.key(),
+        &ctx.accounts.mint_b.key(),
+        &ctx.accounts.token_vault_a.key(),
+        &ctx.accounts.token_vault_b.key(),
+    )?;
+    require_keys_eq!(ctx.accounts.lp_mint.key(), pool.lp_mint, ErrorCode::InvalidPoolState);
+
+    let now = require_valid_until(params.valid_until)?;
+
+    match params.mode {
+        DepositMode::Balanced => {
+            require!(params.amount_a > 0, ErrorCode::InvalidAmount);
+            require!(params.amount_b > 0, ErrorCode::InvalidAmount);
+        }
+        DepositMode::SingleSided => {
+            single_sided_input(params.amount_a, params.amount_b)?;
+        }
+    }
+
+    // Quoted against the last committed pool; the ER re-quotes it when applying the receipt.
+    // A balanced deposit only takes the amounts matching the pool ratio, the rest stays with the provider.
+    let (amount_a, amount_b, lp_tokens_to_mint) = match params.mode {
+        DepositMode::Balanced => {
+            let quote = quote_deposit(
+                pool.reserve_a,
+                pool.reserve_b,
+                pool.total_lp_supply,
+                params.amount_a,
+                params.amount_b,
+            )?;
+            (quote.amount_a, quote.amount_b, quote.lp_tokens)
+        }
+        DepositMode::SingleSided => {
+            let (a_to_b, amount_in) = single_sided_input(params.amount_a, params.amount_b)?;
+            let zap = quote_zap_deposit(
+                pool.reserve_a,
+                pool.reserve_b,
+                pool.total_lp_supply,
+                &pool.fees,
+                a_to_b,
+                amount_in,
+            )?;
+            (params.amount_a, params.amount_b, zap.deposit.lp_tokens)
+        }
+    };
 
     require!(
-        ctx.accounts.provider_token_a_ata.amount >= params.amount_a,
+        ctx.accounts.provider_token_a_ata.amount >= amount_a,
         ErrorCode::InsufficientBalance
     );
 
     require!(
-        ctx.accounts.provider_token_b_ata.amount >= params.amount_b,
+        ctx.accounts.provider_token_b_ata.amount >= amount_b,
         ErrorCode::InsufficientBalance
     );
 
-    let cpi_accounts_token_a = Transfer {
-        from: ctx.accounts.provider_token_a_ata.to_account_info(),
-        to: ctx.accounts.token_vault_a.to_account_info(),
-        authority: ctx.accounts.provider.to_account_info(),
-    };
-
     let cpi_program = ctx.accounts.token_program.to_account_info();
 
-    let cpi_ctx_a = CpiContext::new(cpi_program.clone(), cpi_accounts_token_a);
-    anchor_spl::token::transfer(cpi_ctx_a, params.amount_a)?;
+    if amount_a > 0 {
+        let cpi_accounts_token_a = Transfer {
+            from: ctx.accounts.provider_token_a_ata.to_account_info(),
+            to: ctx.accounts.token_vault_a.to_account_info(),
+            authority: ctx.accounts.provider.to_account_info(),
+        };
 
-    msg!("Transferred {} of Token A to Vault", params.amount_a);
+        let cpi_ctx_a = CpiContext::new(cpi_program.clone(), cpi_accounts_token_a);
+        anchor_spl::token::transfer(cpi_ctx_a, amount_a)?;
 
-    let cpi_accounts_token_b = Transfer {
-        from: ctx.accounts.provider_token_b_ata.to_account_info(),
-        to: ctx.accounts.token_vault_b.to_account_info(),
-        authority: ctx.accounts.provider.to_account_info(),
-    };
+        msg!("Transferred {} of Token A to Vault", amount_a);
+    }
 
-    let cpi_ctx_b = CpiContext::new(cpi_program, cpi_accounts_token_b);
-    anchor_spl::token::transfer(cpi_ctx_b, params.amount_b)?;
-
-    msg!("Transferred {} of Token B to Vault", params.amount_b);
-
-    let lp_tokens_to_mint = if ctx.accounts.lp_mint.supply == 0 {
-        let product = (params.amount_a as u128)
-            .checked_mul(params.amount_b as u128)
-            .ok_or(ErrorCode::MathOverflow)?;
-        
-        let sqrt = (product as f64).sqrt() as u64;
-        sqrt
-    } else {
-        let vault_a_balance = ctx.accounts.token_vault_a.amount - params.amount_a;
-        let vault_b_balance = ctx.accounts.token_vault_b.amount - params.amount_b;
-        
-        require!(vault_a_balance > 0 && vault_b_balance > 0, ErrorCode::InvalidPoolState);
-
-        let lp_from_a = (params.amount_a as u128)
-            .checked_mul(ctx.accounts.lp_mint.supply as u128)
-            .ok_or(ErrorCode::MathOverflow)?
-            .checked_div(vault_a_balance as u128)
-            .ok_or(ErrorCode::MathOverflow)? as u64;
-
-        let lp_from_b = (params.amount_b as u128)
-            .checked_mul(ctx.accounts.lp_mint.supply as u128)
-            .ok_or(ErrorCode::MathOverflow)?
-            .checked_div(vault_b_balance as u128)
-            .ok_or(ErrorCode::MathOverflow)? as u64;
-
-        std::cmp::min(lp_from_a, lp_from_b)
-    };
+    if amount_b > 0 {
+        let cpi_accounts_token_b = Transfer {
+            from: ctx.accounts.provider_token_b_ata.to_account_info(),
+            to: ctx.accounts.token_vault_b.to_account_info(),
+            authority: ctx.accounts.provider.to_account_info(),
+        };
+
+        let cpi_ctx_b = CpiContext::new(cpi_program, cpi_accounts_token_b);
+        anchor_spl::token::transfer(cpi_ctx_b, amount_b)?;
+
+        msg!("Transferred {} of Token B to Vault", amount_b);
+    }
 
     require!(lp_tokens_to_mint >= params.min_lp_tokens, ErrorCode::SlippageExceeded);
 
+    ctx.accounts.receipt_counter.open_receipt(ctx.accounts.pool.key(), ctx.bumps.receipt_counter)?;
+
     let deposit_recept = &mut ctx.accounts.deposit_recept;
-    deposit_recept.pool = params.pool;
+    deposit_recept.pool = ctx.accounts.pool.key();
     deposit_recept.liquidity_provider = ctx.accounts.provider.key();
-    deposit_recept.amount_a = params.amount_a;
-    deposit_recept.amount_b = params.amount_b;
+    deposit_recept.mode = params.mode;
+    deposit_recept.amount_a = amount_a;
+    deposit_recept.amount_b = amount_b;
+    deposit_recept.min_lp_tokens = params.min_lp_tokens;
+    deposit_recept.amount_a_used = 0;
+    deposit_recept.amount_b_used = 0;
     deposit_recept.lp_tokens_minted = lp_tokens_to_mint;
+    deposit_recept.locked_liquidity = 0;
+    deposit_recept.refund_a = 0;
+    deposit_recept.refund_b = 0;
+    deposit_recept.receipt_id = params.receipt_id;
+    deposit_recept.created_at = now;
+    deposit_recept.expires_at = std::cmp::min(now + RECEIPT_TTL_SECONDS, params.valid_until);
+    deposit_recept.status = ReceiptStatus::Created;
+    deposit_recept.bump = ctx.bumps.deposit_recept;
     msg!("Deposit Recept created successfully!");
 
+    emit!(DepositReceiptCreated {
+        pool: deposit_recept.pool,
+        provider: deposit_recept.liquidity_provider,
+        receipt_id: deposit_recept.receipt_id,
+        requested_a: params.amount_a,
+        requested_b: params.amount_b,
+        amount_a,
+        amount_b,
+        lp_tokens_estimate: lp_tokens_to_mint,
+        timestamp: now,
+    });
+
     Ok(())
 }
 
+/// The side a single-sided deposit comes in on: `(a_to_b, amount_in)`. Exactly one amount must be set.
+pub fn single_sided_input(amount_a: u64, amount_b: u64) -> Result<(bool, u64)> {
+    match (amount_a, amount_b) {
+        (amount_in, 0) if amount_in > 0 => Ok((true, amount_in)),
+        (0, amount_in) if amount_in > 0 => Ok((false, amount_in)),
+        _ => err!(ErrorCode::InvalidAmount),
+    }
+}
+
 #[account]
 #[derive(Debug, InitSpace)]
 pub struct DepositRecept {
     pub pool: Pubkey,
     pub liquidity_provider: Pubkey,
+    pub mode: DepositMode,
+    // Transferred into the vaults
     pub amount_a: u64,
     pub amount_b: u64,
+    // The provider's slippage bound, enforced again when the ER re-quotes the deposit
+    pub min_lp_tokens: u64,
+    // Set by the ER: what actually went into the reserves (for a single-sided deposit, after the swap)
+    pub amount_a_used: u64,
+    pub amount_b_used: u64,
+    // The on-chain estimate until the ER sets what the mint handler actually mints
     pub lp_tokens_minted: u64,
+    // Set by the ER when this receipt seeds the pool: MINIMUM_LIQUIDITY minted to `lp_token_account`
+    pub locked_liquidity: u64,
+    // Set by the ER: deposited tokens it didn't use, paid back from the vaults by the mint handler
+    pub refund_a: u64,
+    pub refund_b: u64,
+    pub receipt_id: u64,
+    pub created_at: i64,
+    // The ER refuses the receipt after this; it can then be undelegated and cancelled
+    pub expires_at: i64,
+    pub status: ReceiptStatus,
+    pub bump: u8,
 }
 
 #[delegate]
@@ -193,8 +306,13 @@ pub struct DelegateDepositReceipt<'info> {
     #[account(
         mut,
         del,  // Now we can delegate the existing account
-        seeds = [b"deposit_recept", provider.key().as_ref()],
-        bump
+        seeds = [
+            b"deposit_recept",
+            deposit_recept.pool.as_ref(),
+            provider.key().as_ref(),
+            deposit_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = deposit_recept.bump
     )]
     pub deposit_recept: Account<'info, DepositRecept>,
 }
@@ -209,8 +327,19 @@ pub fn delegate_deposit_receipt(
         validator: Some(validator_key),
     };
 
+    require!(
+        ctx.accounts.deposit_recept.status == ReceiptStatus::Created,
+        ErrorCode::InvalidReceiptStatus
+    );
+
+    // Delegation copies the account data, so the new status has to be written first
+    ctx.accounts.deposit_recept.status = ReceiptStatus::Delegated;
+    ctx.accounts.deposit_recept.exit(&crate::ID)?;
+
     let provider = ctx.accounts.provider.key();
-    let seeds = &[b"deposit_recept", provider.as_ref()];
+    let pool = ctx.accounts.deposit_recept.pool;
+    let receipt_id = ctx.accounts.deposit_recept.receipt_id.to_le_bytes();
+    let seeds = &[b"deposit_recept".as_ref(), pool.as_ref(), provider.as_ref(), receipt_id.as_ref()];
 
     ctx.accounts.delegate_deposit_recept(
         &ctx.accounts.provider,
@@ -225,6 +354,10 @@ pub fn delegate_deposit_receipt(
 }
 
 pub fn commit_and_undelegate_deposit_receipt(ctx: Context<CommitAndUndelegateDepositReceipt>) -> Result<()> {
+    ctx.accounts.deposit_recept.status.require_undelegatable(
+        ctx.accounts.deposit_recept.expires_at,
+        Clock::get()?.unix_timestamp,
+    )?;
 
     commit_and_undelegate_accounts(
         &ctx.accounts.provider, 
@@ -247,8 +380,13 @@ pub struct CommitAndUndelegateDepositReceipt<'info> {
 
     #[account(
         mut,
-        seeds = [b"deposit_recept", provider.key().as_ref()],
-        bump,
+        seeds = [
+            b"deposit_recept",
+            deposit_recept.pool.as_ref(),
+            provider.key().as_ref(),
+            deposit_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = deposit_recept.bump,
     )]
     pub deposit_recept: Account<'info, DepositRecept>,
 
@@ -259,29 +397,3 @@ pub struct CommitAndUndelegateDepositReceipt<'info> {
     /// CHECK: Magic program
     pub magic_program: UncheckedAccount<'info>,
 }
-
-pub fn close_deposit_receipt(_ctx: Context<CloseDepositReceiptInfo>) -> Result<()> {
-    msg!("Depost Receipt account closed successfully!");
-    Ok(())
-}
-
-#[derive(Accounts)]
-pub struct CloseDepositReceiptInfo<'info> {
-    #[account(mut)]
-    pub provider: Signer<'info>,
-
-    #[account(
-        mut,
-        seeds = [b"deposit_recept", provider.key().as_ref()],
-        bump,
-        close = provider
-    )]
-    pub deposit_recept: Account<'info, DepositRecept>,
-
-    pub system_program: Program<'info, System>,
-
-    /// CHECK: the correct pda - this will be moved to the end in the future, meaning you can omit this unless needed
-    pub escrow: UncheckedAccount<'info>,
-    /// CHECK: the correct pda - this will be moved to the end in the future, meaning you can omit this unless needed
-    pub escrow_auth: UncheckedAccount<'info>,
-}
\ No newline at end of file
@@ -1,11 +1,22 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount, Burn};
+use anchor_spl::token::{Mint, Token, TokenAccount, Transfer, Burn, CloseAccount, ThawAccount};
 
+use crate::error::ErrorCode;
+use crate::action_guard::verify_action_escrow;
+use crate::state::receipt::{ReceiptCounter, ReceiptStatus};
+use crate::instructions::remove_liquidity_on_chain::WithdrawRecept;
+
+// Everything settling a withdraw receipt touches, shared by the burn handler and its retry
 #[derive(Accounts)]
-pub struct BurnLpTokens<'info> {
-    #[account()]
-    pub provider: AccountInfo<'info>,
+pub struct SettleWithdraw<'info> {
+    /// CHECK: Receipt owner, bound through the receipt seeds and the escrow authority
+    #[account(mut)]
+    pub provider: UncheckedAccount<'info>,
 
+    /// CHECK: Only its key is used to derive the pool-owned PDAs
+    pub pool: UncheckedAccount<'info>,
+
+    /// CHECK: Transfer authority PDA
     #[account(
         seeds = [b"transfer_authority"],
         bump,
@@ -14,19 +25,76 @@ pub struct BurnLpTokens<'info> {
 
     #[account(
         mut,
-        seeds = [b"lp_token_mint"],
+        seeds = [b"lp_token_mint", pool.key().as_ref()],
         bump
     )]
     pub lp_mint: Account<'info, Mint>,
 
     #[account(
         mut,
-        associated_token::mint = lp_mint,
-        associated_token::authority = provider
+        seeds = [b"withdraw_lp_escrow", withdraw_recept.key().as_ref()],
+        bump,
+        token::mint = lp_mint,
+        token::authority = transfer_authority
     )]
-    pub provider_lp_ata: Account<'info, TokenAccount>,
+    pub withdraw_lp_escrow: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::authority = provider,
+        constraint = provider_token_a_ata.mint == token_vault_a.mint @ ErrorCode::InvalidPoolState
+    )]
+    pub provider_token_a_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::authority = provider,
+        constraint = provider_token_b_ata.mint == token_vault_b.mint @ ErrorCode::InvalidPoolState
+    )]
+    pub provider_token_b_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"withdraw_recept",
+            pool.key().as_ref(),
+            provider.key().as_ref(),
+            withdraw_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = withdraw_recept.bump,
+        close = provider
+    )]
+    pub withdraw_recept: Account<'info, WithdrawRecept>,
+
+    #[account(
+        mut,
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump = receipt_counter.bump
+    )]
+    pub receipt_counter: Account<'info, ReceiptCounter>,
 
     pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct BurnLpTokens<'info> {
+    pub settle: SettleWithdraw<'info>,
 
     /// CHECK: the correct pda - this will be moved to the end in the future, meaning you can omit this unless needed
     pub escrow: UncheckedAccount<'info>,
@@ -34,28 +102,133 @@ pub struct BurnLpTokens<'info> {
     pub escrow_auth: UncheckedAccount<'info>,
 }
 
-pub fn burn_lp_tokens(ctx: Context<BurnLpTokens>, burn_amount: u64) -> Result<()> {
+/// Runs on the base layer after the withdraw receipt is committed and undelegated:
+/// burns the LP locked in the receipt's escrow, pays out the amounts the ER priced,
+/// then closes both the escrow and the receipt, so the payout can only happen once.
+pub fn burn_lp_tokens(ctx: Context<BurnLpTokens>) -> Result<()> {
+    verify_action_escrow(
+        &ctx.accounts.escrow,
+        &ctx.accounts.escrow_auth,
+        &ctx.accounts.settle.provider.key(),
+    )?;
+
+    ctx.accounts.settle.settle(ctx.bumps.settle.transfer_authority)
+}
+
+#[derive(Accounts)]
+pub struct RetryBurnLpTokens<'info> {
+    pub settle: SettleWithdraw<'info>,
+}
+
+/// Base layer: settles a receipt the ER applied but whose burn handler failed after it was
+/// undelegated, exactly as the handler would have. The provider signs it once the receipt expired.
+pub fn retry_burn_lp_tokens(ctx: Context<RetryBurnLpTokens>) -> Result<()> {
+    let settle = &mut ctx.accounts.settle;
+    require!(settle.provider.is_signer, ErrorCode::Unauthorized);
+
+    settle.withdraw_recept.status.require_retryable(
+        settle.withdraw_recept.expires_at,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    settle.settle(ctx.bumps.settle.transfer_authority)
+}
+
+impl<'info> SettleWithdraw<'info> {
+    pub(crate) fn settle(&mut self, transfer_authority_bump: u8) -> Result<()> {
+        self.receipt_counter.close_receipt()?;
+
+        let withdraw_recept = &mut self.withdraw_recept;
+
+        require!(withdraw_recept.status == ReceiptStatus::AppliedInER, ErrorCode::InvalidReceiptStatus);
+
+        let burn_amount = withdraw_recept.lp_tokens_to_burn;
+        let amount_a = withdraw_recept.amount_a_withdrawn;
+        let amount_b = withdraw_recept.amount_b_withdrawn;
+        withdraw_recept.status = ReceiptStatus::Settled;
+
+        require!(self.withdraw_lp_escrow.amount == burn_amount, ErrorCode::InvalidReceipt);
+
+        msg!("Burning {} escrowed LP tokens on-chain", burn_amount);
+
+        let authority_seeds = &[
+            b"transfer_authority".as_ref(),
+            &[transfer_authority_bump],
+        ];
+        let signer_seeds = &[&authority_seeds[..]];
 
-    msg!("Burning {} LP tokens on-chain from provider", burn_amount);
+        // The escrow is frozen while the withdrawal is in flight; a frozen account can't be burned from
+        let cpi_accounts_thaw = ThawAccount {
+            account: self.withdraw_lp_escrow.to_account_info(),
+            mint: self.lp_mint.to_account_info(),
+            authority: self.transfer_authority.to_account_info(),
+        };
 
-    let authority_seeds = &[
-        b"transfer_authority".as_ref(),
-        &[ctx.bumps.transfer_authority],
-    ];
-    let signer_seeds = &[&authority_seeds[..]];
+        let cpi_ctx_thaw = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts_thaw,
+            signer_seeds,
+        );
+        anchor_spl::token::thaw_account(cpi_ctx_thaw)?;
 
-    let cpi_accounts = Burn {
-        mint: ctx.accounts.lp_mint.to_account_info(),
-        from: ctx.accounts.provider_lp_ata.to_account_info(),
-        authority: ctx.accounts.transfer_authority.to_account_info()
-    };
+        let cpi_accounts = Burn {
+            mint: self.lp_mint.to_account_info(),
+            from: self.withdraw_lp_escrow.to_account_info(),
+            authority: self.transfer_authority.to_account_info()
+        };
 
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        let cpi_program = self.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
 
-    anchor_spl::token::burn(cpi_ctx, burn_amount)?;
+        anchor_spl::token::burn(cpi_ctx, burn_amount)?;
 
-    msg!("Successfully Burned {} LP tokens on-chain", burn_amount);
+        // A single-token withdrawal leaves the other side at zero
+        if amount_a > 0 {
+            let cpi_accounts_a = Transfer {
+                from: self.token_vault_a.to_account_info(),
+                to: self.provider_token_a_ata.to_account_info(),
+                authority: self.transfer_authority.to_account_info(),
+            };
 
-    Ok(())
-}
\ No newline at end of file
+            let cpi_ctx_a = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts_a,
+                signer_seeds,
+            );
+            anchor_spl::token::transfer(cpi_ctx_a, amount_a)?;
+        }
+
+        if amount_b > 0 {
+            let cpi_accounts_b = Transfer {
+                from: self.token_vault_b.to_account_info(),
+                to: self.provider_token_b_ata.to_account_info(),
+                authority: self.transfer_authority.to_account_info(),
+            };
+
+            let cpi_ctx_b = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts_b,
+                signer_seeds,
+            );
+            anchor_spl::token::transfer(cpi_ctx_b, amount_b)?;
+        }
+
+        // The escrow is empty now; its rent goes back to the provider
+        let cpi_accounts_close = CloseAccount {
+            account: self.withdraw_lp_escrow.to_account_info(),
+            destination: self.provider.to_account_info(),
+            authority: self.transfer_authority.to_account_info(),
+        };
+
+        let cpi_ctx_close = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts_close,
+            signer_seeds,
+        );
+        anchor_spl::token::close_account(cpi_ctx_close)?;
+
+        msg!("Successfully burned {} LP tokens and paid out A={}, B={}", burn_amount, amount_a, amount_b);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,395 @@
+// Base layer only: a receipt can be cancelled once it is back under this program
+// (never delegated, or undelegated after expiring) and the ER never applied it.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount, Transfer, CloseAccount, ThawAccount};
+
+use crate::error::ErrorCode;
+use crate::state::receipt::{ReceiptCounter, ReceiptStatus};
+use crate::events::{DepositReceiptCancelled, WithdrawReceiptCancelled, SwapReceiptCancelled};
+use crate::instructions::add_liquidity_on_chain::DepositRecept;
+use crate::instructions::remove_liquidity_on_chain::WithdrawRecept;
+use crate::instructions::swap_on_chain::SwapRecept;
+
+#[derive(Accounts)]
+pub struct CancelDepositReceipt<'info> {
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    /// CHECK: Bound to the receipt; only its key is used to derive the pool-owned PDAs
+    #[account(address = deposit_recept.pool)]
+    pub pool: UncheckedAccount<'info>,
+
+    pub mint_a: Account<'info, Mint>,
+    pub mint_b: Account<'info, Mint>,
+
+    /// CHECK: Transfer authority PDA
+    #[account(
+        seeds = [b"transfer_authority"],
+        bump,
+    )]
+    pub transfer_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump,
+        token::mint = mint_a,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump,
+        token::mint = mint_b,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = provider
+    )]
+    pub provider_token_a_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = provider
+    )]
+    pub provider_token_b_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"deposit_recept",
+            pool.key().as_ref(),
+            provider.key().as_ref(),
+            deposit_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = deposit_recept.bump,
+        close = provider
+    )]
+    pub deposit_recept: Account<'info, DepositRecept>,
+
+    #[account(
+        mut,
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump = receipt_counter.bump
+    )]
+    pub receipt_counter: Account<'info, ReceiptCounter>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelWithdrawReceipt<'info> {
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    /// CHECK: Bound to the receipt; only its key is used to derive the pool-owned PDAs
+    #[account(address = withdraw_recept.pool)]
+    pub pool: UncheckedAccount<'info>,
+
+    /// CHECK: Transfer authority PDA
+    #[account(
+        seeds = [b"transfer_authority"],
+        bump,
+    )]
+    pub transfer_authority: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"lp_token_mint", pool.key().as_ref()],
+        bump
+    )]
+    pub lp_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = lp_mint,
+        associated_token::authority = provider
+    )]
+    pub provider_token_lp_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"withdraw_lp_escrow", withdraw_recept.key().as_ref()],
+        bump,
+        token::mint = lp_mint,
+        token::authority = transfer_authority
+    )]
+    pub withdraw_lp_escrow: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"withdraw_recept",
+            pool.key().as_ref(),
+            provider.key().as_ref(),
+            withdraw_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = withdraw_recept.bump,
+        close = provider
+    )]
+    pub withdraw_recept: Account<'info, WithdrawRecept>,
+
+    #[account(
+        mut,
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump = receipt_counter.bump
+    )]
+    pub receipt_counter: Account<'info, ReceiptCounter>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSwapReceipt<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: Bound to the receipt; only its key is used to derive the pool-owned PDAs
+    #[account(address = swap_recept.pool)]
+    pub pool: UncheckedAccount<'info>,
+
+    #[account(address = swap_recept.mint_a)]
+    pub mint_a: Account<'info, Mint>,
+    #[account(address = swap_recept.mint_b)]
+    pub mint_b: Account<'info, Mint>,
+
+    /// CHECK: Transfer authority PDA
+    #[account(
+        seeds = [b"transfer_authority"],
+        bump,
+    )]
+    pub transfer_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump,
+        token::mint = mint_a,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump,
+        token::mint = mint_b,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = user
+    )]
+    pub user_token_a_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = user
+    )]
+    pub user_token_b_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"swap_recept",
+            pool.key().as_ref(),
+            user.key().as_ref(),
+            swap_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = swap_recept.bump,
+        close = user
+    )]
+    pub swap_recept: Account<'info, SwapRecept>,
+
+    #[account(
+        mut,
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump = receipt_counter.bump
+    )]
+    pub receipt_counter: Account<'info, ReceiptCounter>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Refunds the deposited tokens from the vaults and closes the receipt.
+pub fn cancel_deposit_receipt(ctx: Context<CancelDepositReceipt>) -> Result<()> {
+    require!(ctx.accounts.deposit_recept.status.is_cancellable(), ErrorCode::InvalidReceiptStatus);
+
+    ctx.accounts.receipt_counter.close_receipt()?;
+    ctx.accounts.deposit_recept.status = ReceiptStatus::Cancelled;
+    let deposit_recept = &ctx.accounts.deposit_recept;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"transfer_authority".as_ref(),
+        &[ctx.bumps.transfer_authority],
+    ]];
+
+    let cpi_accounts_a = Transfer {
+        from: ctx.accounts.token_vault_a.to_account_info(),
+        to: ctx.accounts.provider_token_a_ata.to_account_info(),
+        authority: ctx.accounts.transfer_authority.to_account_info(),
+    };
+
+    let cpi_ctx_a = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        cpi_accounts_a,
+        signer_seeds,
+    );
+    anchor_spl::token::transfer(cpi_ctx_a, deposit_recept.amount_a)?;
+
+    let cpi_accounts_b = Transfer {
+        from: ctx.accounts.token_vault_b.to_account_info(),
+        to: ctx.accounts.provider_token_b_ata.to_account_info(),
+        authority: ctx.accounts.transfer_authority.to_account_info(),
+    };
+
+    let cpi_ctx_b = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        cpi_accounts_b,
+        signer_seeds,
+    );
+    anchor_spl::token::transfer(cpi_ctx_b, deposit_recept.amount_b)?;
+
+    emit!(DepositReceiptCancelled {
+        pool: deposit_recept.pool,
+        provider: deposit_recept.liquidity_provider,
+        receipt_id: deposit_recept.receipt_id,
+        amount_a: deposit_recept.amount_a,
+        amount_b: deposit_recept.amount_b,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!(
+        "Deposit receipt cancelled, refunded A={}, B={}",
+        deposit_recept.amount_a,
+        deposit_recept.amount_b
+    );
+
+    Ok(())
+}
+
+/// Drops a withdrawal the ER never applied: nothing was paid out, so the escrow is thawed,
+/// its LP tokens go back to the provider and both the escrow and the receipt are closed.
+pub fn cancel_withdraw_receipt(ctx: Context<CancelWithdrawReceipt>) -> Result<()> {
+    require!(ctx.accounts.withdraw_recept.status.is_cancellable(), ErrorCode::InvalidReceiptStatus);
+
+    ctx.accounts.receipt_counter.close_receipt()?;
+    ctx.accounts.withdraw_recept.status = ReceiptStatus::Cancelled;
+    let withdraw_recept = &ctx.accounts.withdraw_recept;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"transfer_authority".as_ref(),
+        &[ctx.bumps.transfer_authority],
+    ]];
+
+    let cpi_accounts_thaw = ThawAccount {
+        account: ctx.accounts.withdraw_lp_escrow.to_account_info(),
+        mint: ctx.accounts.lp_mint.to_account_info(),
+        authority: ctx.accounts.transfer_authority.to_account_info(),
+    };
+
+    let cpi_ctx_thaw = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        cpi_accounts_thaw,
+        signer_seeds,
+    );
+    anchor_spl::token::thaw_account(cpi_ctx_thaw)?;
+
+    let cpi_accounts_return = Transfer {
+        from: ctx.accounts.withdraw_lp_escrow.to_account_info(),
+        to: ctx.accounts.provider_token_lp_ata.to_account_info(),
+        authority: ctx.accounts.transfer_authority.to_account_info(),
+    };
+
+    let cpi_ctx_return = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        cpi_accounts_return,
+        signer_seeds,
+    );
+    anchor_spl::token::transfer(cpi_ctx_return, ctx.accounts.withdraw_lp_escrow.amount)?;
+
+    let cpi_accounts_close = CloseAccount {
+        account: ctx.accounts.withdraw_lp_escrow.to_account_info(),
+        destination: ctx.accounts.provider.to_account_info(),
+        authority: ctx.accounts.transfer_authority.to_account_info(),
+    };
+
+    let cpi_ctx_close = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        cpi_accounts_close,
+        signer_seeds,
+    );
+    anchor_spl::token::close_account(cpi_ctx_close)?;
+
+    emit!(WithdrawReceiptCancelled {
+        pool: withdraw_recept.pool,
+        provider: withdraw_recept.liquidity_provider,
+        receipt_id: withdraw_recept.receipt_id,
+        lp_tokens: withdraw_recept.lp_tokens_to_burn,
+        amount_a: withdraw_recept.amount_a_withdrawn,
+        amount_b: withdraw_recept.amount_b_withdrawn,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Withdraw receipt cancelled, returned {} LP tokens", withdraw_recept.lp_tokens_to_burn);
+
+    Ok(())
+}
+
+/// Drops a swap the ER never executed: the whole escrowed input goes back to the user
+/// and the receipt is closed.
+pub fn cancel_swap_receipt(ctx: Context<CancelSwapReceipt>) -> Result<()> {
+    require!(ctx.accounts.swap_recept.status.is_cancellable(), ErrorCode::InvalidReceiptStatus);
+
+    ctx.accounts.receipt_counter.close_receipt()?;
+    ctx.accounts.swap_recept.status = ReceiptStatus::Cancelled;
+    let swap_recept = &ctx.accounts.swap_recept;
+
+    let (vault_in, user_in) = if swap_recept.a_to_b {
+        (&ctx.accounts.token_vault_a, &ctx.accounts.user_token_a_ata)
+    } else {
+        (&ctx.accounts.token_vault_b, &ctx.accounts.user_token_b_ata)
+    };
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"transfer_authority".as_ref(),
+        &[ctx.bumps.transfer_authority],
+    ]];
+
+    let cpi_accounts = Transfer {
+        from: vault_in.to_account_info(),
+        to: user_in.to_account_info(),
+        authority: ctx.accounts.transfer_authority.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        cpi_accounts,
+        signer_seeds,
+    );
+    anchor_spl::token::transfer(cpi_ctx, swap_recept.amount_escrowed)?;
+
+    emit!(SwapReceiptCancelled {
+        pool: swap_recept.pool,
+        user: swap_recept.user,
+        receipt_id: swap_recept.receipt_id,
+        a_to_b: swap_recept.a_to_b,
+        amount: swap_recept.amount_escrowed,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Swap receipt cancelled, refunded {} tokens", swap_recept.amount_escrowed);
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+// Base layer only: the pool must be undelegated so the owed amounts include every fee
+// accrued in the ER (committed with the pool) as well as on the base layer.
+
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{Mint, Token, TokenAccount, Transfer};
+
+use crate::state::pool::Pool;
+use crate::error::ErrorCode;
+use crate::events::ProtocolFeesClaimed;
+
+#[derive(Accounts)]
+pub struct ClaimProtocolFees<'info> {
+    #[account(
+        mut,
+        constraint = (
+            claimer.key() == pool.admin || claimer.key() == pool.fees.fee_recipient
+        ) @ ErrorCode::Unauthorized
+    )]
+    pub claimer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", pool.name.as_bytes()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    /// CHECK: Receives the fees, bound to the pool's fee config
+    #[account(address = pool.fees.fee_recipient)]
+    pub fee_recipient: UncheckedAccount<'info>,
+
+    /// CHECK: Transfer authority PDA
+    #[account(
+        seeds = [b"transfer_authority"],
+        bump,
+    )]
+    pub transfer_authority: UncheckedAccount<'info>,
+
+    #[account(address = pool.token_a)]
+    pub mint_a: Account<'info, Mint>,
+
+    #[account(address = pool.token_b)]
+    pub mint_b: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump = pool.token_a_vault_bump,
+        token::mint = mint_a,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump = pool.token_b_vault_bump,
+        token::mint = mint_b,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = claimer,
+        associated_token::mint = mint_a,
+        associated_token::authority = fee_recipient
+    )]
+    pub fee_recipient_token_a_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = claimer,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_recipient
+    )]
+    pub fee_recipient_token_b_ata: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn claim_protocol_fees(ctx: Context<ClaimProtocolFees>) -> Result<()> {
+    let amount_a = ctx.accounts.pool.protocol_fees_owed_a;
+    let amount_b = ctx.accounts.pool.protocol_fees_owed_b;
+
+    require!(amount_a > 0 || amount_b > 0, ErrorCode::InvalidAmount);
+
+    require!(ctx.accounts.token_vault_a.amount >= amount_a, ErrorCode::InsufficientFunds);
+    require!(ctx.accounts.token_vault_b.amount >= amount_b, ErrorCode::InsufficientFunds);
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"transfer_authority".as_ref(),
+        &[ctx.bumps.transfer_authority],
+    ]];
+
+    if amount_a > 0 {
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.token_vault_a.to_account_info(),
+            to: ctx.accounts.fee_recipient_token_a_ata.to_account_info(),
+            authority: ctx.accounts.transfer_authority.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        anchor_spl::token::transfer(cpi_ctx, amount_a)?;
+    }
+
+    if amount_b > 0 {
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.token_vault_b.to_account_info(),
+            to: ctx.accounts.fee_recipient_token_b_ata.to_account_info(),
+            authority: ctx.accounts.transfer_authority.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        anchor_spl::token::transfer(cpi_ctx, amount_b)?;
+    }
+
+    let pool = &mut ctx.accounts.pool;
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    pool.protocol_fees_owed_a = 0;
+    pool.protocol_fees_owed_b = 0;
+    pool.updated_at = timestamp;
+
+    emit!(ProtocolFeesClaimed {
+        pool: pool.key(),
+        fee_recipient: pool.fees.fee_recipient,
+        amount_a,
+        amount_b,
+        timestamp,
+    });
+
+    msg!("Claimed protocol fees: A={}, B={}", amount_a, amount_b);
+
+    Ok(())
+}
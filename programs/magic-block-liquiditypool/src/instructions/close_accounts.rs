@@ -0,0 +1,71 @@
+// Base layer only: reclaims rent from accounts nothing references anymore. Both accounts
+// have to be back under this program, so delegated ones must be undelegated first.
+
+use anchor_lang::prelude::*;
+
+use crate::error::ErrorCode;
+use crate::state::liquidity_provider::LiquidityProvider;
+use crate::state::pool::Pool;
+// Glob import: the composite `SettleWithdraw` needs its generated client modules in scope
+use crate::instructions::burn_lp_tokens::*;
+
+#[derive(Accounts)]
+pub struct CloseLiquidityProvider<'info> {
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity_provider_account_info", provider.key().as_ref()],
+        bump = liquidity_provider.bump,
+        close = provider
+    )]
+    pub liquidity_provider: Account<'info, LiquidityProvider>,
+}
+
+#[derive(Accounts)]
+pub struct CloseWithdrawReceipt<'info> {
+    // The provider, or the pool admin so an abandoned receipt can't hold up `reconcile_pool`
+    pub authority: Signer<'info>,
+
+    pub settle: SettleWithdraw<'info>,
+}
+
+/// Closes the header once every position under it is empty. The totals include the legacy
+/// slots, so a provider that still has unmigrated liquidity can't close either.
+pub fn close_liquidity_provider(ctx: Context<CloseLiquidityProvider>) -> Result<()> {
+    let liquidity_provider = &ctx.accounts.liquidity_provider;
+
+    require!(liquidity_provider.total_lp_tokens == 0, ErrorCode::PositionNotEmpty);
+    require!(
+        liquidity_provider.legacy_pools_info.iter().all(|slot| slot.lp_tokens == 0),
+        ErrorCode::PositionNotEmpty
+    );
+
+    msg!("Liquidity Provider account closed");
+
+    Ok(())
+}
+
+/// Closes a straggler withdraw receipt: one the ER applied whose burn handler failed after
+/// it was undelegated, leaving its LP frozen in the escrow. The ER already took the withdrawal
+/// out of the reserves, so it is settled the way the handler would have, paying the provider
+/// and closing the escrow, the receipt and its slot in the receipt counter.
+pub fn close_withdraw_receipt(ctx: Context<CloseWithdrawReceipt>) -> Result<()> {
+    let authority = ctx.accounts.authority.key();
+    let settle = &mut ctx.accounts.settle;
+
+    if authority != settle.provider.key() {
+        let pool = Pool::load_checked(&settle.pool)?;
+        require_keys_eq!(authority, pool.admin, ErrorCode::Unauthorized);
+    }
+
+    settle.withdraw_recept.status.require_retryable(
+        settle.withdraw_recept.expires_at,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    msg!("Closing withdraw receipt {}", settle.withdraw_recept.receipt_id);
+
+    settle.settle(ctx.bumps.settle.transfer_authority)
+}
@@ -0,0 +1,96 @@
+// Runs in the ER: commits the pool (and provider) state back to the base layer,
+// optionally handing the accounts back so the pool can be maintained, migrated or re-delegated.
+
+use anchor_lang::prelude::*;
+
+use ephemeral_rollups_sdk::anchor::commit;
+use ephemeral_rollups_sdk::ephem::{commit_accounts, commit_and_undelegate_accounts};
+
+use crate::state::pool::Pool;
+use crate::state::liquidity_provider::LiquidityProvider;
+use crate::error::ErrorCode;
+
+#[commit]
+#[derive(Accounts)]
+pub struct CommitPool<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", pool.name.as_bytes()],
+        bump = pool.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub pool: Account<'info, Pool>,
+
+    /// CHECK: Magic context account
+    #[account(mut)]
+    pub magic_context: UncheckedAccount<'info>,
+
+    /// CHECK: Magic program
+    pub magic_program: UncheckedAccount<'info>,
+}
+
+#[commit]
+#[derive(Accounts)]
+pub struct CommitAndUndelegateLiquidityProvider<'info> {
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity_provider_account_info", provider.key().as_ref()],
+        bump = liquidity_provider.bump
+    )]
+    pub liquidity_provider: Account<'info, LiquidityProvider>,
+
+    /// CHECK: Magic context account
+    #[account(mut)]
+    pub magic_context: UncheckedAccount<'info>,
+
+    /// CHECK: Magic program
+    pub magic_program: UncheckedAccount<'info>,
+}
+
+pub fn commit_pool(ctx: Context<CommitPool>) -> Result<()> {
+
+    commit_accounts(
+        &ctx.accounts.admin,
+        vec![&ctx.accounts.pool.to_account_info()],
+        &ctx.accounts.magic_context,
+        &ctx.accounts.magic_program,
+    )?;
+
+    msg!("Pool {} committed to the base layer", ctx.accounts.pool.name);
+
+    Ok(())
+}
+
+pub fn commit_and_undelegate_pool(ctx: Context<CommitPool>) -> Result<()> {
+
+    commit_and_undelegate_accounts(
+        &ctx.accounts.admin,
+        vec![&ctx.accounts.pool.to_account_info()],
+        &ctx.accounts.magic_context,
+        &ctx.accounts.magic_program,
+    )?;
+
+    msg!("Pool {} committed and undelegated", ctx.accounts.pool.name);
+
+    Ok(())
+}
+
+pub fn commit_and_undelegate_liquidity_provider(ctx: Context<CommitAndUndelegateLiquidityProvider>) -> Result<()> {
+
+    commit_and_undelegate_accounts(
+        &ctx.accounts.provider,
+        vec![&ctx.accounts.liquidity_provider.to_account_info()],
+        &ctx.accounts.magic_context,
+        &ctx.accounts.magic_program,
+    )?;
+
+    msg!("Liquidity Provider account committed and undelegated");
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use anchor_lang::prelude::*;
+
+use ephemeral_rollups_sdk::anchor::{commit, delegate};
+use ephemeral_rollups_sdk::cpi::DelegateConfig;
+use ephemeral_rollups_sdk::ephem::commit_and_undelegate_accounts;
+
+use crate::state::lp_position::LpPosition;
+
+#[delegate]
+#[derive(Accounts)]
+pub struct DelegateLpPosition<'info> {
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    #[account(
+        mut,
+        del,
+        seeds = [b"lp_position", lp_position.pool.as_ref(), provider.key().as_ref()],
+        bump = lp_position.bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+}
+
+pub fn delegate_lp_position(ctx: Context<DelegateLpPosition>, commit_frequency: u32, validator_key: Pubkey) -> Result<()> {
+    let delegate_config = DelegateConfig {
+        commit_frequency_ms: commit_frequency,
+        validator: Some(validator_key),
+    };
+
+    let pool = ctx.accounts.lp_position.pool;
+    let provider_key = ctx.accounts.provider.key();
+    let seeds = &[b"lp_position".as_ref(), pool.as_ref(), provider_key.as_ref()];
+
+    ctx.accounts.delegate_lp_position(&ctx.accounts.provider, seeds, delegate_config)?;
+
+    msg!("LP position delegated successfully!");
+
+    Ok(())
+}
+
+#[commit]
+#[derive(Accounts)]
+pub struct CommitAndUndelegateLpPosition<'info> {
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"lp_position", lp_position.pool.as_ref(), provider.key().as_ref()],
+        bump = lp_position.bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+
+    /// CHECK: Magic context account
+    #[account(mut)]
+    pub magic_context: UncheckedAccount<'info>,
+
+    /// CHECK: Magic program
+    pub magic_program: UncheckedAccount<'info>,
+}
+
+pub fn commit_and_undelegate_lp_position(ctx: Context<CommitAndUndelegateLpPosition>) -> Result<()> {
+    commit_and_undelegate_accounts(
+        &ctx.accounts.provider,
+        vec![&ctx.accounts.lp_position.to_account_info()],
+        &ctx.accounts.magic_context,
+        &ctx.accounts.magic_program,
+    )?;
+
+    msg!("LP position committed and undelegated");
+
+    Ok(())
+}
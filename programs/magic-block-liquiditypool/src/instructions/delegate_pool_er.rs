@@ -4,6 +4,7 @@ use ephemeral_rollups_sdk::anchor::delegate;
 use ephemeral_rollups_sdk::cpi::DelegateConfig;
 
 use crate::state::pool::Pool;
+use crate::error::ErrorCode;
 
 #[delegate]
 #[derive(Accounts)]
@@ -15,7 +16,8 @@ pub struct DelegatePool<'info> {
         mut,
         del,
         seeds = [b"pool", pool.name.as_bytes()],
-        bump
+        bump,
+        constraint = pool.admin == payer.key() @ ErrorCode::Unauthorized
     )]
     pub pool: Account<'info, Pool>,
 }
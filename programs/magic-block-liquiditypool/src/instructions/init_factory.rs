@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ErrorCode;
+use crate::program::MagicBlockLiquiditypool;
+use crate::state::factory::Factory;
+
+#[derive(Accounts)]
+pub struct InitializeFactory<'info> {
+    // The factory is a singleton, so only the program's upgrade authority may create it
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Factory::INIT_SPACE,
+        seeds = [b"factory"],
+        bump
+    )]
+    pub factory: Account<'info, Factory>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ ErrorCode::Unauthorized)]
+    pub program: Program<'info, MagicBlockLiquiditypool>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ ErrorCode::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn initialize_factory(ctx: Context<InitializeFactory>) -> Result<()> {
+    msg!("Initializing Pool Factory...");
+
+    let factory = &mut ctx.accounts.factory;
+
+    factory.pool_count = 0;
+    factory.created_at = Clock::get()?.unix_timestamp;
+    factory.bump = ctx.bumps.factory;
+
+    msg!("Pool Factory Initialized Successfully!");
+
+    Ok(())
+}
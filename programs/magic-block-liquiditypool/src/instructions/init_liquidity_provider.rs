@@ -28,7 +28,7 @@ pub fn initialize_liquidity_provider(ctx: Context<InitializeLiquidityProvider>)
     liquidity_provider_account.provider = ctx.accounts.provider.key();
     liquidity_provider_account.total_liquidity_provided = 0;
     liquidity_provider_account.total_lp_tokens = 0;
-    liquidity_provider_account.liquidity_pools_info = [LiquidityPoolInfo::default(); 10];
+    liquidity_provider_account.legacy_pools_info = [LiquidityPoolInfo::default(); 10];
     liquidity_provider_account.latest_liquidity_provided_on = 0;
     liquidity_provider_account.bump = ctx.bumps.liquidity_provider_account_info;
 
@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+use crate::state::pool::Pool;
+use crate::state::lp_position::LpPosition;
+
+#[derive(Accounts)]
+pub struct InitializeLpPosition<'info> {
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    /// CHECK: Loaded with `Pool::load_checked`; the pool may be delegated to the ER
+    pub pool: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = provider,
+        space = 8 + LpPosition::INIT_SPACE,
+        seeds = [b"lp_position", pool.key().as_ref(), provider.key().as_ref()],
+        bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn initialize_lp_position(ctx: Context<InitializeLpPosition>) -> Result<()> {
+    let pool = Pool::load_checked(&ctx.accounts.pool)?;
+
+    let lp_position = &mut ctx.accounts.lp_position;
+    lp_position.provider = ctx.accounts.provider.key();
+    lp_position.pool = ctx.accounts.pool.key();
+    lp_position.lp_mint = pool.lp_mint;
+    lp_position.liquidity_amount = 0;
+    lp_position.lp_tokens = 0;
+    lp_position.last_updated_at = Clock::get()?.unix_timestamp;
+    lp_position.bump = ctx.bumps.lp_position;
+
+    msg!("Initialized LP position for pool {}", pool.name);
+
+    Ok(())
+}
@@ -2,6 +2,8 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::{Mint, Token, TokenAccount};
 
 use crate::state::pool::{Pool, PoolStatus, FeeConfig};
+use crate::state::factory::{Factory, PoolMapping, PoolRegistryPage};
+use crate::error::ErrorCode;
 
 use anchor_spl::metadata::mpl_token_metadata::{
     instructions::CreateMetadataAccountV3CpiBuilder,
@@ -21,17 +23,54 @@ pub struct InitializeLiquidityPool<'info> {
     )]
     pub transfer_authority: AccountInfo<'info>,
 
+    #[account(
+        mut,
+        seeds = [b"factory"],
+        bump = factory.bump
+    )]
+    pub factory: Account<'info, Factory>,
+
     pub mint_a: Account<'info, Mint>,
 
+    #[account(
+        constraint = mint_a.key() < mint_b.key() @ ErrorCode::InvalidMintOrder
+    )]
     pub mint_b: Account<'info, Mint>,
 
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Pool::INIT_SPACE,
+        seeds = [b"pool", params.name.as_bytes()],
+        bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + PoolMapping::INIT_SPACE,
+        seeds = [b"pool_mapping", mint_a.key().as_ref(), mint_b.key().as_ref(), params.trade_fees.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub pool_mapping: Account<'info, PoolMapping>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + PoolRegistryPage::INIT_SPACE,
+        seeds = [b"pool_registry", Factory::registry_page_for(factory.pool_count).to_le_bytes().as_ref()],
+        bump
+    )]
+    pub pool_registry_page: Account<'info, PoolRegistryPage>,
+
     #[account(
         init,
         payer = admin,
         mint::authority = transfer_authority,
         mint::freeze_authority = transfer_authority,
         mint::decimals = 6,
-        seeds = [b"lp_token_mint"],
+        seeds = [b"lp_token_mint", pool.key().as_ref()],
         bump
     )]
     pub lp_mint: Account<'info, Mint>,
@@ -39,26 +78,17 @@ pub struct InitializeLiquidityPool<'info> {
     #[account(
         init,
         payer = admin,
-        seeds = [b"lp_token_account"],
+        seeds = [b"lp_token_account", pool.key().as_ref()],
         bump,
         token::mint = lp_mint,
         token::authority = transfer_authority
     )]
     pub lp_token_account: Account<'info, TokenAccount>,
 
-    #[account(
-        init_if_needed,
-        payer = admin,
-        space = Pool::INIT_SPACE,
-        seeds = [b"pool", params.name.as_bytes()],
-        bump
-    )]
-    pub pool: Account<'info, Pool>,
-
     #[account(
         init,
         payer = admin,
-        seeds = [b"token_account_a", mint_a.key().as_ref()],
+        seeds = [b"token_account_a", pool.key().as_ref()],
         bump,
         token::mint = mint_a,
         token::authority = transfer_authority
@@ -68,7 +98,7 @@ pub struct InitializeLiquidityPool<'info> {
     #[account(
         init,
         payer = admin,
-        seeds = [b"token_account_b", mint_b.key().as_ref()],
+        seeds = [b"token_account_b", pool.key().as_ref()],
         bump,
         token::mint = mint_b,
         token::authority = transfer_authority
@@ -90,7 +120,6 @@ pub struct InitializeLiquidityPool<'info> {
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct AddPoolParams {
-    pub pool_id: u64,
     pub name: String,
     pub max_aum_usd: u64,
     pub metadata_title: String,
@@ -103,6 +132,14 @@ pub struct AddPoolParams {
 pub fn initialize_liquidity_pool(ctx: Context<InitializeLiquidityPool>, params: AddPoolParams) -> Result<()> {
     msg!("Initializing Liquidity Pool...");
 
+    FeeConfig::validate_fees(params.trade_fees, params.protocol_fees)?;
+
+    let factory = &mut ctx.accounts.factory;
+    let pool_id = factory.pool_count;
+    factory.pool_count = factory.pool_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
     let liquidity_pool_config = &mut ctx.accounts.pool;
 
     liquidity_pool_config.fees = FeeConfig {
@@ -110,6 +147,7 @@ pub fn initialize_liquidity_pool(ctx: Context<InitializeLiquidityPool>, params:
         protocol_fee_bps: params.protocol_fees,
         fee_recipient: params.fee_recipient,
     };
+    liquidity_pool_config.fee_tier = params.trade_fees;
 
     liquidity_pool_config.status = PoolStatus {
         is_active: true,
@@ -119,7 +157,9 @@ pub fn initialize_liquidity_pool(ctx: Context<InitializeLiquidityPool>, params:
 
     liquidity_pool_config.version = 1;
     liquidity_pool_config.authority = *ctx.accounts.transfer_authority.key;
-    liquidity_pool_config.pool_id = params.pool_id;
+    liquidity_pool_config.admin = ctx.accounts.admin.key();
+    liquidity_pool_config.pending_admin = Pubkey::default();
+    liquidity_pool_config.pool_id = pool_id;
     liquidity_pool_config.name = params.name;
     liquidity_pool_config.lp_mint = ctx.accounts.lp_mint.key();
     liquidity_pool_config.token_a = ctx.accounts.mint_a.key();
@@ -138,6 +178,21 @@ pub fn initialize_liquidity_pool(ctx: Context<InitializeLiquidityPool>, params:
 
     msg!("Liquidity Pool Initialized Successfully!");
 
+    let pool_mapping = &mut ctx.accounts.pool_mapping;
+    pool_mapping.mint_a = ctx.accounts.mint_a.key();
+    pool_mapping.mint_b = ctx.accounts.mint_b.key();
+    pool_mapping.fee_tier = ctx.accounts.pool.fee_tier;
+    pool_mapping.pool = ctx.accounts.pool.key();
+    pool_mapping.pool_id = pool_id;
+    pool_mapping.bump = ctx.bumps.pool_mapping;
+
+    let pool_registry_page = &mut ctx.accounts.pool_registry_page;
+    pool_registry_page.page = Factory::registry_page_for(pool_id);
+    pool_registry_page.pools.push(ctx.accounts.pool.key());
+    pool_registry_page.bump = ctx.bumps.pool_registry_page;
+
+    msg!("Registered pool {} on registry page {}", pool_id, pool_registry_page.page);
+
     msg!("Creating LP Token Metadata...");
 
     let signer_seeds: &[&[&[u8]]] = &[&[
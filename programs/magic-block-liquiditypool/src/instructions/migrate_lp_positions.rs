@@ -0,0 +1,160 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{allocate, assign, create_account, transfer, Allocate, Assign, CreateAccount, Transfer};
+
+use crate::error::ErrorCode;
+use crate::state::liquidity_provider::{LiquidityPoolInfo, LiquidityProvider};
+use crate::state::lp_position::LpPosition;
+
+#[derive(Accounts)]
+pub struct MigrateLpPositions<'info> {
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    // Has to be back on the base layer, like every position it is migrated into
+    #[account(
+        mut,
+        seeds = [b"liquidity_provider_account_info", provider.key().as_ref()],
+        bump = liquidity_provider.bump
+    )]
+    pub liquidity_provider: Account<'info, LiquidityProvider>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Base layer only: moves every non-empty legacy slot on the header into its `LpPosition`.
+/// The positions are passed as remaining accounts in slot order, one per non-empty slot;
+/// missing ones are created, existing ones have the slot added to them. The aggregate
+/// totals don't change, since they already include the slots.
+pub fn migrate_lp_positions<'info>(ctx: Context<'_, '_, 'info, 'info, MigrateLpPositions<'info>>) -> Result<()> {
+    let provider_key = ctx.accounts.provider.key();
+    let now = Clock::get()?.unix_timestamp;
+    let mut positions = ctx.remaining_accounts.iter();
+    let mut migrated = 0u8;
+
+    for slot in ctx.accounts.liquidity_provider.legacy_pools_info.iter_mut() {
+        if slot.pool == Pubkey::default() {
+            continue;
+        }
+
+        let position_info = positions.next().ok_or(ErrorCode::InvalidPosition)?;
+        let (expected_position, bump) = Pubkey::find_program_address(
+            &[b"lp_position", slot.pool.as_ref(), provider_key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(position_info.key(), expected_position, ErrorCode::InvalidPosition);
+        require!(position_info.is_writable, ErrorCode::InvalidPosition);
+
+        let mut position = if position_info.owner == &crate::ID {
+            LpPosition::try_deserialize(&mut &position_info.try_borrow_data()?[..])?
+        } else {
+            // A delegated position is owned by the delegation program and has to be undelegated first
+            require_keys_eq!(*position_info.owner, System::id(), ErrorCode::InvalidPosition);
+
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                b"lp_position".as_ref(),
+                slot.pool.as_ref(),
+                provider_key.as_ref(),
+                &[bump],
+            ]];
+
+            create_position_account(
+                &ctx.accounts.provider.to_account_info(),
+                position_info,
+                &ctx.accounts.system_program.to_account_info(),
+                signer_seeds,
+            )?;
+
+            LpPosition {
+                provider: provider_key,
+                pool: slot.pool,
+                lp_mint: slot.token_mint,
+                liquidity_amount: 0,
+                lp_tokens: 0,
+                last_updated_at: now,
+                bump,
+            }
+        };
+
+        position.liquidity_amount = position.liquidity_amount
+            .checked_add(slot.liquidity_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        position.lp_tokens = position.lp_tokens
+            .checked_add(slot.lp_tokens)
+            .ok_or(ErrorCode::MathOverflow)?;
+        position.last_updated_at = now;
+
+        position.try_serialize(&mut &mut position_info.try_borrow_mut_data()?[..])?;
+
+        *slot = LiquidityPoolInfo::default();
+        migrated += 1;
+    }
+
+    msg!("Migrated {} legacy pool slots into LP positions", migrated);
+
+    Ok(())
+}
+
+// Same as Anchor's `init`: anyone can send lamports to the PDA beforehand, which makes
+// `create_account` fail, so a funded address is topped up, allocated and assigned instead
+fn create_position_account<'info>(
+    payer: &AccountInfo<'info>,
+    position_info: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let space = 8 + LpPosition::INIT_SPACE;
+    let rent = Rent::get()?.minimum_balance(space);
+    let lamports = position_info.lamports();
+
+    if lamports == 0 {
+        return create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                CreateAccount {
+                    from: payer.clone(),
+                    to: position_info.clone(),
+                },
+                signer_seeds,
+            ),
+            rent,
+            space as u64,
+            &crate::ID,
+        );
+    }
+
+    let shortfall = rent.saturating_sub(lamports);
+    if shortfall > 0 {
+        transfer(
+            CpiContext::new(
+                system_program.clone(),
+                Transfer {
+                    from: payer.clone(),
+                    to: position_info.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+
+    allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            Allocate {
+                account_to_allocate: position_info.clone(),
+            },
+            signer_seeds,
+        ),
+        space as u64,
+    )?;
+
+    assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            Assign {
+                account_to_assign: position_info.clone(),
+            },
+            signer_seeds,
+        ),
+        &crate::ID,
+    )
+}
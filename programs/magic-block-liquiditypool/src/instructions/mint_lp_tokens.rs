@@ -1,12 +1,20 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount};
+use anchor_spl::token::{Mint, Token, TokenAccount, Transfer};
 
+use crate::error::ErrorCode;
+use crate::action_guard::verify_action_escrow;
+use crate::state::receipt::{ReceiptCounter, ReceiptStatus};
+use crate::instructions::add_liquidity_on_chain::DepositRecept;
+
+// Everything settling a deposit receipt touches, shared by the mint handler and its retry
 #[derive(Accounts)]
-pub struct MintLpTokens<'info> {
-    #[account(
-        // mut
-    )]
-    pub provider: AccountInfo<'info>,
+pub struct SettleDeposit<'info> {
+    /// CHECK: Receipt owner, bound through the receipt seeds and the escrow authority
+    #[account(mut)]
+    pub provider: UncheckedAccount<'info>,
+
+    /// CHECK: Only its key is used to derive the pool-owned PDAs
+    pub pool: UncheckedAccount<'info>,
 
     /// CHECK: Transfer authority PDA
     #[account(
@@ -17,7 +25,7 @@ pub struct MintLpTokens<'info> {
 
     #[account(
         mut,
-        seeds = [b"lp_token_mint"],
+        seeds = [b"lp_token_mint", pool.key().as_ref()],
         bump
     )]
     pub lp_mint: Account<'info, Mint>,
@@ -29,7 +37,71 @@ pub struct MintLpTokens<'info> {
     )]
     pub provider_lp_ata: Account<'info, TokenAccount>,
 
+    #[account(
+        mut,
+        seeds = [b"lp_token_account", pool.key().as_ref()],
+        bump,
+        token::mint = lp_mint,
+        token::authority = transfer_authority
+    )]
+    pub lp_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::authority = provider,
+        constraint = provider_token_a_ata.mint == token_vault_a.mint @ ErrorCode::InvalidPoolState
+    )]
+    pub provider_token_a_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::authority = provider,
+        constraint = provider_token_b_ata.mint == token_vault_b.mint @ ErrorCode::InvalidPoolState
+    )]
+    pub provider_token_b_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"deposit_recept",
+            pool.key().as_ref(),
+            provider.key().as_ref(),
+            deposit_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = deposit_recept.bump,
+        close = provider
+    )]
+    pub deposit_recept: Account<'info, DepositRecept>,
+
+    #[account(
+        mut,
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump = receipt_counter.bump
+    )]
+    pub receipt_counter: Account<'info, ReceiptCounter>,
+
     pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MintLpTokens<'info> {
+    pub settle: SettleDeposit<'info>,
 
     /// CHECK: the correct pda - this will be moved to the end in the future, meaning you can omit this unless needed
     pub escrow: UncheckedAccount<'info>,
@@ -37,27 +109,124 @@ pub struct MintLpTokens<'info> {
     pub escrow_auth: UncheckedAccount<'info>,
 }
 
-pub fn mint_lp_tokens(ctx: Context<MintLpTokens>, mint_amount: u64) -> Result<()> {
-    msg!("Minting {} LP tokens on-chain to provider", mint_amount);
+/// Runs on the base layer after the deposit receipt is committed and undelegated:
+/// mints the LP tokens the ER credited to the receipt (plus the locked minimum liquidity
+/// on the pool's first deposit), refunds whatever the ER didn't use, then closes it.
+pub fn mint_lp_tokens(ctx: Context<MintLpTokens>) -> Result<()> {
+    verify_action_escrow(
+        &ctx.accounts.escrow,
+        &ctx.accounts.escrow_auth,
+        &ctx.accounts.settle.provider.key(),
+    )?;
 
-    let authority_seeds = &[
-        b"transfer_authority".as_ref(),
-        &[ctx.bumps.transfer_authority],
-    ];
-    let signer_seeds = &[&authority_seeds[..]];
+    ctx.accounts.settle.settle(ctx.bumps.settle.transfer_authority)
+}
+
+#[derive(Accounts)]
+pub struct RetryMintLpTokens<'info> {
+    pub settle: SettleDeposit<'info>,
+}
+
+/// Base layer: settles a receipt the ER applied but whose mint handler failed after it was
+/// undelegated, exactly as the handler would have. The provider signs it once the receipt expired.
+pub fn retry_mint_lp_tokens(ctx: Context<RetryMintLpTokens>) -> Result<()> {
+    let settle = &mut ctx.accounts.settle;
+    require!(settle.provider.is_signer, ErrorCode::Unauthorized);
+
+    settle.deposit_recept.status.require_retryable(
+        settle.deposit_recept.expires_at,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    settle.settle(ctx.bumps.settle.transfer_authority)
+}
 
-    let cpi_accounts = anchor_spl::token::MintTo {
-        mint: ctx.accounts.lp_mint.to_account_info(),
-        to: ctx.accounts.provider_lp_ata.to_account_info(),
-        authority: ctx.accounts.transfer_authority.to_account_info(),
-    };
+impl<'info> SettleDeposit<'info> {
+    fn settle(&mut self, transfer_authority_bump: u8) -> Result<()> {
+        self.receipt_counter.close_receipt()?;
 
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        let deposit_recept = &mut self.deposit_recept;
 
-    anchor_spl::token::mint_to(cpi_ctx, mint_amount)?;
+        require!(deposit_recept.status == ReceiptStatus::AppliedInER, ErrorCode::InvalidReceiptStatus);
 
-    msg!("Successfully minted {} LP tokens on-chain", mint_amount);
+        let mint_amount = deposit_recept.lp_tokens_minted;
+        let locked_liquidity = deposit_recept.locked_liquidity;
+        let refund_a = deposit_recept.refund_a;
+        let refund_b = deposit_recept.refund_b;
+        deposit_recept.status = ReceiptStatus::Settled;
 
-    Ok(())
-}
\ No newline at end of file
+        msg!("Minting {} LP tokens on-chain to provider", mint_amount);
+
+        let authority_seeds = &[
+            b"transfer_authority".as_ref(),
+            &[transfer_authority_bump],
+        ];
+        let signer_seeds = &[&authority_seeds[..]];
+
+        let cpi_accounts = anchor_spl::token::MintTo {
+            mint: self.lp_mint.to_account_info(),
+            to: self.provider_lp_ata.to_account_info(),
+            authority: self.transfer_authority.to_account_info(),
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        anchor_spl::token::mint_to(cpi_ctx, mint_amount)?;
+
+        if locked_liquidity > 0 {
+            let cpi_accounts_locked = anchor_spl::token::MintTo {
+                mint: self.lp_mint.to_account_info(),
+                to: self.lp_token_account.to_account_info(),
+                authority: self.transfer_authority.to_account_info(),
+            };
+
+            let cpi_ctx_locked = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts_locked,
+                signer_seeds,
+            );
+            anchor_spl::token::mint_to(cpi_ctx_locked, locked_liquidity)?;
+
+            msg!("Locked {} LP tokens in the pool's LP token account", locked_liquidity);
+        }
+
+        if refund_a > 0 {
+            let cpi_accounts_refund_a = Transfer {
+                from: self.token_vault_a.to_account_info(),
+                to: self.provider_token_a_ata.to_account_info(),
+                authority: self.transfer_authority.to_account_info(),
+            };
+
+            let cpi_ctx_refund_a = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts_refund_a,
+                signer_seeds,
+            );
+            anchor_spl::token::transfer(cpi_ctx_refund_a, refund_a)?;
+        }
+
+        if refund_b > 0 {
+            let cpi_accounts_refund_b = Transfer {
+                from: self.token_vault_b.to_account_info(),
+                to: self.provider_token_b_ata.to_account_info(),
+                authority: self.transfer_authority.to_account_info(),
+            };
+
+            let cpi_ctx_refund_b = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts_refund_b,
+                signer_seeds,
+            );
+            anchor_spl::token::transfer(cpi_ctx_refund_b, refund_b)?;
+        }
+
+        if refund_a > 0 || refund_b > 0 {
+            msg!("Refunded unused deposit: A={}, B={}", refund_a, refund_b);
+        }
+
+        msg!("Successfully minted {} LP tokens on-chain", mint_amount);
+
+        Ok(())
+    }
+}
@@ -1,18 +1,33 @@
 pub mod initialize;
 pub use initialize::*;
 
+pub mod init_factory;
+pub use init_factory::*;
+
 pub mod init_pool;
 pub use init_pool::*;
 
 pub mod delegate_pool_er;
 pub use delegate_pool_er::*;
 
+pub mod commit_pool;
+pub use commit_pool::*;
+
 pub mod init_liquidity_provider;
 pub use init_liquidity_provider::*;
 
 pub mod delegate_liquidity_provider;
 pub use delegate_liquidity_provider::*;
 
+pub mod init_lp_position;
+pub use init_lp_position::*;
+
+pub mod delegate_lp_position;
+pub use delegate_lp_position::*;
+
+pub mod migrate_lp_positions;
+pub use migrate_lp_positions::*;
+
 pub mod add_liquidity_er;
 pub use add_liquidity_er::*;
 
@@ -22,9 +37,6 @@ pub use add_liquidity_on_chain::*;
 pub mod mint_lp_tokens;
 pub use mint_lp_tokens::*;
 
-pub mod commit_and_undelegate_deposit_receipt;
-pub use commit_and_undelegate_deposit_receipt::*;
-
 pub mod remove_liquidity_on_chain;
 pub use remove_liquidity_on_chain::*;
 
@@ -32,4 +44,28 @@ pub mod remove_liquidity_er;
 pub use remove_liquidity_er::*;
 
 pub mod burn_lp_tokens;
-pub use burn_lp_tokens::*;
\ No newline at end of file
+pub use burn_lp_tokens::*;
+
+pub mod cancel_receipt;
+pub use cancel_receipt::*;
+
+pub mod close_accounts;
+pub use close_accounts::*;
+
+pub mod swap;
+pub use swap::*;
+
+pub mod swap_on_chain;
+pub use swap_on_chain::*;
+
+pub mod swap_er;
+pub use swap_er::*;
+
+pub mod pool_admin;
+pub use pool_admin::*;
+
+pub mod claim_protocol_fees;
+pub use claim_protocol_fees::*;
+
+pub mod reconcile_pool;
+pub use reconcile_pool::*;
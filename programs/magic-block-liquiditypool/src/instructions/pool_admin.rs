@@ -0,0 +1,208 @@
+// Works on the base layer and inside the ER: the pool is the only account touched.
+
+use anchor_lang::prelude::*;
+
+use crate::state::pool::{Pool, FeeConfig};
+use crate::error::ErrorCode;
+use crate::events::{PoolPaused, PoolResumed, PoolDeactivated, PoolFeesUpdated, FeeRecipientUpdated, AdminProposed, AdminAccepted};
+
+#[derive(Accounts)]
+pub struct PoolAdmin<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", pool.name.as_bytes()],
+        bump = pool.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub pool: Account<'info, Pool>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptPoolAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", pool.name.as_bytes()],
+        bump = pool.bump,
+        has_one = pending_admin @ ErrorCode::Unauthorized
+    )]
+    pub pool: Account<'info, Pool>,
+}
+
+#[derive(Clone, AnchorDeserialize, AnchorSerialize)]
+pub struct UpdatePoolFeesParams {
+    // `None` keeps the current fee
+    pub trade_fee_bps: Option<u16>,
+    pub protocol_fee_bps: Option<u16>,
+}
+
+pub fn pause_pool(ctx: Context<PoolAdmin>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    require!(pool.status.is_active, ErrorCode::PoolNotActive);
+    require!(!pool.status.is_paused, ErrorCode::PoolPaused);
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    pool.status.is_paused = true;
+    pool.updated_at = timestamp;
+
+    emit!(PoolPaused {
+        pool: pool.key(),
+        authority: ctx.accounts.admin.key(),
+        timestamp,
+    });
+
+    msg!("Pool {} paused", pool.name);
+
+    Ok(())
+}
+
+pub fn resume_pool(ctx: Context<PoolAdmin>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    require!(pool.status.is_active, ErrorCode::PoolNotActive);
+    require!(pool.status.is_paused, ErrorCode::InvalidPoolState);
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    pool.status.is_paused = false;
+    pool.updated_at = timestamp;
+
+    emit!(PoolResumed {
+        pool: pool.key(),
+        authority: ctx.accounts.admin.key(),
+        timestamp,
+    });
+
+    msg!("Pool {} resumed", pool.name);
+
+    Ok(())
+}
+
+// Deactivation is permanent: a deactivated pool can't be resumed
+pub fn deactivate_pool(ctx: Context<PoolAdmin>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    require!(pool.status.is_active, ErrorCode::PoolNotActive);
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    pool.status.is_active = false;
+    pool.updated_at = timestamp;
+
+    emit!(PoolDeactivated {
+        pool: pool.key(),
+        authority: ctx.accounts.admin.key(),
+        timestamp,
+    });
+
+    msg!("Pool {} deactivated", pool.name);
+
+    Ok(())
+}
+
+// The `PoolMapping` is keyed by `pool.fee_tier`, which stays put, so the live fees can move within the caps
+pub fn update_pool_fees(ctx: Context<PoolAdmin>, params: UpdatePoolFeesParams) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    let old_trade_fee_bps = pool.fees.trade_fee_bps;
+    let old_protocol_fee_bps = pool.fees.protocol_fee_bps;
+    let new_trade_fee_bps = params.trade_fee_bps.unwrap_or(old_trade_fee_bps);
+    let new_protocol_fee_bps = params.protocol_fee_bps.unwrap_or(old_protocol_fee_bps);
+
+    FeeConfig::validate_fees(new_trade_fee_bps, new_protocol_fee_bps)?;
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    pool.fees.trade_fee_bps = new_trade_fee_bps;
+    pool.fees.protocol_fee_bps = new_protocol_fee_bps;
+    pool.updated_at = timestamp;
+
+    emit!(PoolFeesUpdated {
+        pool: pool.key(),
+        authority: ctx.accounts.admin.key(),
+        old_trade_fee_bps,
+        new_trade_fee_bps,
+        old_protocol_fee_bps,
+        new_protocol_fee_bps,
+        timestamp,
+    });
+
+    msg!(
+        "Pool fees updated: trade {} -> {} bps, protocol {} -> {} bps",
+        old_trade_fee_bps,
+        new_trade_fee_bps,
+        old_protocol_fee_bps,
+        new_protocol_fee_bps
+    );
+
+    Ok(())
+}
+
+pub fn set_fee_recipient(ctx: Context<PoolAdmin>, new_fee_recipient: Pubkey) -> Result<()> {
+    require!(new_fee_recipient != Pubkey::default(), ErrorCode::InvalidFeeConfig);
+
+    let pool = &mut ctx.accounts.pool;
+
+    let old_fee_recipient = pool.fees.fee_recipient;
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    pool.fees.fee_recipient = new_fee_recipient;
+    pool.updated_at = timestamp;
+
+    emit!(FeeRecipientUpdated {
+        pool: pool.key(),
+        authority: ctx.accounts.admin.key(),
+        old_fee_recipient,
+        new_fee_recipient,
+        timestamp,
+    });
+
+    msg!("Fee recipient rotated to {}", new_fee_recipient);
+
+    Ok(())
+}
+
+// Proposing the default pubkey cancels a pending handover
+pub fn propose_admin(ctx: Context<PoolAdmin>, new_admin: Pubkey) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    pool.pending_admin = new_admin;
+    pool.updated_at = timestamp;
+
+    emit!(AdminProposed {
+        pool: pool.key(),
+        admin: pool.admin,
+        pending_admin: new_admin,
+        timestamp,
+    });
+
+    msg!("Proposed {} as pool admin", new_admin);
+
+    Ok(())
+}
+
+pub fn accept_admin(ctx: Context<AcceptPoolAdmin>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    require!(pool.pending_admin != Pubkey::default(), ErrorCode::Unauthorized);
+
+    let old_admin = pool.admin;
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    pool.admin = pool.pending_admin;
+    pool.pending_admin = Pubkey::default();
+    pool.updated_at = timestamp;
+
+    emit!(AdminAccepted {
+        pool: pool.key(),
+        old_admin,
+        new_admin: pool.admin,
+        timestamp,
+    });
+
+    msg!("Pool admin handed over from {} to {}", old_admin, pool.admin);
+
+    Ok(())
+}
@@ -0,0 +1,331 @@
+// Compares the committed pool state against what the base layer actually holds:
+//   vault = reserve + protocol fees owed + deposits not yet applied in the ER
+//           + deposit refunds not yet paid out
+//           + withdrawals applied in the ER but not yet paid out
+//           + swap input still owed back to the user + swap output not yet paid
+//   lp mint supply = total_lp_supply - LP applied in the ER but not yet minted
+//           + escrowed LP applied in the ER but not yet burned
+// Outstanding receipts are passed as remaining accounts, and may still be delegated. All of them
+// are required: their number has to match the pool's receipt counter.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, TokenAccount};
+
+use ephemeral_rollups_sdk::cpi::DELEGATION_PROGRAM_ID;
+
+use crate::state::pool::Pool;
+use crate::state::receipt::{ReceiptCounter, ReceiptStatus};
+use crate::error::ErrorCode;
+use crate::events::PoolReconciled;
+use crate::instructions::add_liquidity_on_chain::DepositRecept;
+use crate::instructions::remove_liquidity_on_chain::WithdrawRecept;
+use crate::instructions::swap_on_chain::SwapRecept;
+
+#[derive(Accounts)]
+pub struct ReconcilePool<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", pool.name.as_bytes()],
+        bump = pool.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        seeds = [b"lp_token_mint", pool.key().as_ref()],
+        bump = pool.lp_mint_bump
+    )]
+    pub lp_mint: Account<'info, Mint>,
+
+    #[account(
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump = pool.token_a_vault_bump
+    )]
+    pub token_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump = pool.token_b_vault_bump
+    )]
+    pub token_vault_b: Account<'info, TokenAccount>,
+
+    /// CHECK: Loaded with `ReceiptCounter::load_open_receipts`; absent until the pool's first receipt
+    #[account(
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump
+    )]
+    pub receipt_counter: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CheckPoolInvariants<'info> {
+    /// CHECK: Loaded with `Pool::load_checked`; the pool may be delegated to the ER
+    pub pool: UncheckedAccount<'info>,
+
+    /// CHECK: Pool LP mint, resolved from the pool key
+    #[account(
+        seeds = [b"lp_token_mint", pool.key().as_ref()],
+        bump
+    )]
+    pub lp_mint: Account<'info, Mint>,
+
+    #[account(
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump
+    )]
+    pub token_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump
+    )]
+    pub token_vault_b: Account<'info, TokenAccount>,
+
+    /// CHECK: Loaded with `ReceiptCounter::load_open_receipts`; absent until the pool's first receipt
+    #[account(
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump
+    )]
+    pub receipt_counter: UncheckedAccount<'info>,
+}
+
+pub struct Reconciliation {
+    // Positive when the vaults hold more than the pool accounts for
+    pub discrepancy_a: i128,
+    pub discrepancy_b: i128,
+    pub lp_discrepancy: i128,
+}
+
+impl Reconciliation {
+    pub fn is_consistent(&self) -> bool {
+        self.discrepancy_a == 0 && self.discrepancy_b == 0 && self.lp_discrepancy == 0
+    }
+
+    // The vaults can't cover what the pool owes, or LP exists that the pool never issued.
+    // A surplus isn't: anyone can send tokens to a vault or burn their own LP.
+    pub fn is_short(&self) -> bool {
+        self.discrepancy_a < 0 || self.discrepancy_b < 0 || self.lp_discrepancy > 0
+    }
+}
+
+/// Admin-only: flags the pool migrating and paused when the vaults fall short of the committed state,
+/// and clears the migrating flag once they cover it again. A surplus is only reported.
+/// Resuming stays an explicit `resume_pool`.
+pub fn reconcile_pool<'info>(ctx: Context<'_, '_, 'info, 'info, ReconcilePool<'info>>) -> Result<()> {
+    let pool_key = ctx.accounts.pool.key();
+
+    let reconciliation = compute_reconciliation(
+        pool_key,
+        &ctx.accounts.pool,
+        ctx.accounts.token_vault_a.amount,
+        ctx.accounts.token_vault_b.amount,
+        ctx.accounts.lp_mint.supply,
+        ReceiptCounter::load_open_receipts(&ctx.accounts.receipt_counter)?,
+        ctx.remaining_accounts,
+    )?;
+
+    let pool = &mut ctx.accounts.pool;
+    let timestamp = Clock::get()?.unix_timestamp;
+
+    if reconciliation.is_short() {
+        pool.status.is_migrating = true;
+        pool.status.is_paused = true;
+        msg!("Pool {} is short of its vaults, pausing", pool.name);
+    } else {
+        pool.status.is_migrating = false;
+        if !reconciliation.is_consistent() {
+            msg!("Pool {} holds a surplus over its committed state", pool.name);
+        }
+    }
+    pool.updated_at = timestamp;
+
+    emit_reconciliation(
+        pool_key,
+        ctx.accounts.token_vault_a.amount,
+        ctx.accounts.token_vault_b.amount,
+        ctx.accounts.lp_mint.supply,
+        &reconciliation,
+        timestamp,
+    );
+
+    Ok(())
+}
+
+/// Read-only: reports the same discrepancy as `reconcile_pool` without touching the pool.
+/// Meant to be simulated; a delegated pool is checked against its last committed state.
+pub fn check_pool_invariants<'info>(ctx: Context<'_, '_, 'info, 'info, CheckPoolInvariants<'info>>) -> Result<()> {
+    let pool_info = ctx.accounts.pool.to_account_info();
+    let pool = Pool::load_checked(&pool_info)?;
+
+    let reconciliation = compute_reconciliation(
+        pool_info.key(),
+        &pool,
+        ctx.accounts.token_vault_a.amount,
+        ctx.accounts.token_vault_b.amount,
+        ctx.accounts.lp_mint.supply,
+        ReceiptCounter::load_open_receipts(&ctx.accounts.receipt_counter)?,
+        ctx.remaining_accounts,
+    )?;
+
+    emit_reconciliation(
+        pool_info.key(),
+        ctx.accounts.token_vault_a.amount,
+        ctx.accounts.token_vault_b.amount,
+        ctx.accounts.lp_mint.supply,
+        &reconciliation,
+        Clock::get()?.unix_timestamp,
+    );
+
+    Ok(())
+}
+
+pub fn compute_reconciliation(
+    pool_key: Pubkey,
+    pool: &Pool,
+    vault_a: u64,
+    vault_b: u64,
+    lp_supply: u64,
+    open_receipts: u64,
+    receipts: &[AccountInfo],
+) -> Result<Reconciliation> {
+    // Each receipt below is checked to be a distinct open receipt of this pool, so matching
+    // the count means none was left out
+    require!(receipts.len() as u64 == open_receipts, ErrorCode::IncompleteReceipts);
+
+    let mut expected_a = pool.reserve_a as i128 + pool.protocol_fees_owed_a as i128;
+    let mut expected_b = pool.reserve_b as i128 + pool.protocol_fees_owed_b as i128;
+    let mut expected_lp_supply = pool.total_lp_supply as i128;
+
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(receipts.len());
+
+    for receipt in receipts {
+        require!(!seen.contains(receipt.key), ErrorCode::InvalidReceipt);
+        seen.push(receipt.key());
+
+        require!(
+            receipt.owner == &crate::ID || receipt.owner == &DELEGATION_PROGRAM_ID,
+            ErrorCode::InvalidReceipt
+        );
+
+        let data = receipt.try_borrow_data()?;
+        require!(data.len() >= 8, ErrorCode::InvalidReceipt);
+
+        if data[..8] == *DepositRecept::DISCRIMINATOR {
+            let deposit = DepositRecept::try_deserialize(&mut &data[..])?;
+            require!(deposit.pool == pool_key, ErrorCode::InvalidReceipt);
+            check_receipt_key(receipt, &[
+                b"deposit_recept",
+                pool_key.as_ref(),
+                deposit.liquidity_provider.as_ref(),
+                deposit.receipt_id.to_le_bytes().as_ref(),
+                &[deposit.bump],
+            ])?;
+
+            match deposit.status {
+                ReceiptStatus::Created | ReceiptStatus::Delegated => {
+                    expected_a += deposit.amount_a as i128;
+                    expected_b += deposit.amount_b as i128;
+                }
+                ReceiptStatus::AppliedInER => {
+                    // Refunds are still in the vaults until the mint handler pays them out
+                    expected_a += deposit.refund_a as i128;
+                    expected_b += deposit.refund_b as i128;
+                    expected_lp_supply -= deposit.lp_tokens_minted as i128 + deposit.locked_liquidity as i128;
+                }
+                ReceiptStatus::Settled | ReceiptStatus::Cancelled => {}
+            }
+        } else if data[..8] == *WithdrawRecept::DISCRIMINATOR {
+            let withdraw = WithdrawRecept::try_deserialize(&mut &data[..])?;
+            require!(withdraw.pool == pool_key, ErrorCode::InvalidReceipt);
+            check_receipt_key(receipt, &[
+                b"withdraw_recept",
+                pool_key.as_ref(),
+                withdraw.liquidity_provider.as_ref(),
+                withdraw.receipt_id.to_le_bytes().as_ref(),
+                &[withdraw.bump],
+            ])?;
+
+            // Until the ER applies it the LP just sits in escrow; after that the payout
+            // and the burn are still pending in the vaults and the mint
+            if withdraw.status == ReceiptStatus::AppliedInER {
+                expected_a += withdraw.amount_a_withdrawn as i128;
+                expected_b += withdraw.amount_b_withdrawn as i128;
+                expected_lp_supply += withdraw.lp_tokens_to_burn as i128;
+            }
+        } else if data[..8] == *SwapRecept::DISCRIMINATOR {
+            let swap = SwapRecept::try_deserialize(&mut &data[..])?;
+            require!(swap.pool == pool_key, ErrorCode::InvalidReceipt);
+            check_receipt_key(receipt, &[
+                b"swap_recept",
+                pool_key.as_ref(),
+                swap.user.as_ref(),
+                swap.receipt_id.to_le_bytes().as_ref(),
+                &[swap.bump],
+            ])?;
+
+            // Executed swaps are already in the reserves: only the refund and the output are still in the vaults
+            let (owed_in, owed_out) = match swap.status {
+                ReceiptStatus::Created | ReceiptStatus::Delegated => (swap.amount_escrowed as i128, 0),
+                ReceiptStatus::AppliedInER => {
+                    (swap.amount_escrowed as i128 - swap.amount_in as i128, swap.amount_out as i128)
+                }
+                ReceiptStatus::Settled | ReceiptStatus::Cancelled => (0, 0),
+            };
+
+            if swap.a_to_b {
+                expected_a += owed_in;
+                expected_b += owed_out;
+            } else {
+                expected_b += owed_in;
+                expected_a += owed_out;
+            }
+        } else {
+            return err!(ErrorCode::InvalidReceipt);
+        }
+    }
+
+    Ok(Reconciliation {
+        discrepancy_a: vault_a as i128 - expected_a,
+        discrepancy_b: vault_b as i128 - expected_b,
+        lp_discrepancy: lp_supply as i128 - expected_lp_supply,
+    })
+}
+
+// Delegated receipts are owned by the delegation program, so the seeds are what binds them to this program
+fn check_receipt_key(receipt: &AccountInfo, seeds_with_bump: &[&[u8]]) -> Result<()> {
+    let expected_key = Pubkey::create_program_address(seeds_with_bump, &crate::ID)
+        .map_err(|_| error!(ErrorCode::InvalidReceipt))?;
+    require!(expected_key == receipt.key(), ErrorCode::InvalidReceipt);
+    Ok(())
+}
+
+fn emit_reconciliation(
+    pool: Pubkey,
+    vault_a: u64,
+    vault_b: u64,
+    lp_supply: u64,
+    reconciliation: &Reconciliation,
+    timestamp: i64,
+) {
+    emit!(PoolReconciled {
+        pool,
+        vault_a,
+        vault_b,
+        lp_supply,
+        discrepancy_a: reconciliation.discrepancy_a,
+        discrepancy_b: reconciliation.discrepancy_b,
+        lp_discrepancy: reconciliation.lp_discrepancy,
+        is_consistent: reconciliation.is_consistent(),
+        is_short: reconciliation.is_short(),
+        timestamp,
+    });
+
+    msg!(
+        "Reconciliation: A off by {}, B off by {}, LP off by {}",
+        reconciliation.discrepancy_a,
+        reconciliation.discrepancy_b,
+        reconciliation.lp_discrepancy
+    );
+}
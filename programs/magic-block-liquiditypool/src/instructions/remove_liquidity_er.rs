@@ -1,8 +1,13 @@
 use anchor_lang::prelude::*;
 
-use crate::state::liquidity_provider::{LiquidityPoolInfo, LiquidityProvider};
+use crate::state::liquidity_provider::LiquidityProvider;
+use crate::state::lp_position::LpPosition;
 use crate::state::pool::Pool;
 use crate::error::ErrorCode;
+use crate::math::curve::{quote_withdraw, quote_withdraw_single};
+use crate::instructions::swap::apply_swap_to_reserves;
+use crate::state::receipt::ReceiptStatus;
+use crate::instructions::remove_liquidity_on_chain::{WithdrawRecept, WithdrawMode};
 
 #[derive(Accounts)]
 pub struct RemoveLiquidityER<'info> {
@@ -16,6 +21,13 @@ pub struct RemoveLiquidityER<'info> {
     )]
     pub liquidity_provider: Account<'info, LiquidityProvider>,
 
+    #[account(
+        mut,
+        seeds = [b"lp_position", pool.key().as_ref(), provider.key().as_ref()],
+        bump = lp_position.bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+
     #[account(
         mut,
         seeds = [b"pool", pool.name.as_bytes()],
@@ -23,6 +35,20 @@ pub struct RemoveLiquidityER<'info> {
     )]
     pub pool: Account<'info, Pool>,
 
+    #[account(
+        mut,
+        seeds = [
+            b"withdraw_recept",
+            pool.key().as_ref(),
+            provider.key().as_ref(),
+            withdraw_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = withdraw_recept.bump,
+        constraint = withdraw_recept.pool == pool.key() @ ErrorCode::InvalidReceipt,
+        constraint = withdraw_recept.liquidity_provider == provider.key() @ ErrorCode::InvalidReceipt
+    )]
+    pub withdraw_recept: Account<'info, WithdrawRecept>,
+
     pub system_program: Program<'info, System>,
 }
 
@@ -38,64 +64,84 @@ pub fn remove_liquidity_er(ctx: Context<RemoveLiquidityER>, params: RemoveLiquid
 
     let pool = &mut ctx.accounts.pool;
     let liquidity_provider = &mut ctx.accounts.liquidity_provider;
+    let withdraw_recept = &mut ctx.accounts.withdraw_recept;
 
-    require!(pool.status.is_active, ErrorCode::PoolNotActive);
-    require!(!pool.status.is_paused, ErrorCode::PoolPaused);
+    // Exits stay open on a paused or deactivated pool, only the single-token swap-back is gated
+    require!(withdraw_recept.status == ReceiptStatus::Delegated, ErrorCode::InvalidReceiptStatus);
+    require!(
+        Clock::get()?.unix_timestamp <= withdraw_recept.expires_at,
+        ErrorCode::ReceiptExpired
+    );
+    require!(params.lp_tokens == withdraw_recept.lp_tokens_to_burn, ErrorCode::InvalidReceipt);
 
     msg!("Removing liquidity in ER (state only)...");
 
-    let reserve_a = pool.reserve_a;
-    let reserve_b = pool.reserve_b;
-    let total_lp_supply = pool.total_lp_supply;
-
-    let provider_pool_info = liquidity_provider
-        .liquidity_pools_info
-        .iter()
-        .find(|info| info.pool == pool.key())
-        .ok_or(ErrorCode::ProviderNotFound)?;
-
-    require!(provider_pool_info.lp_tokens >= params.lp_tokens, ErrorCode::InsufficientLpTokens);
-
-    let amount_a_to_withdraw = (params.lp_tokens as u128)
-        .checked_mul(reserve_a as u128)
-        .ok_or(ErrorCode::MathOverflow)?
-        .checked_div(total_lp_supply as u128)
-        .ok_or(ErrorCode::MathOverflow)? as u64;
-
-    let amount_b_to_withdraw = (params.lp_tokens as u128)
-        .checked_mul(reserve_b as u128)
-        .ok_or(ErrorCode::MathOverflow)?
-        .checked_div(total_lp_supply as u128)
-        .ok_or(ErrorCode::MathOverflow)? as u64;
-
+    require!(ctx.accounts.lp_position.lp_tokens >= params.lp_tokens, ErrorCode::InsufficientLpTokens);
+
+    let (pro_rata, swap_back, amount_a_to_withdraw, amount_b_to_withdraw) = match withdraw_recept.mode {
+        WithdrawMode::Balanced => {
+            let quote = quote_withdraw(pool.reserve_a, pool.reserve_b, pool.total_lp_supply, params.lp_tokens)?;
+            (quote, None, quote.amount_a, quote.amount_b)
+        }
+        WithdrawMode::SingleToken { to_a } => {
+            pool.require_tradable()?;
+            let quote = quote_withdraw_single(
+                pool.reserve_a,
+                pool.reserve_b,
+                pool.total_lp_supply,
+                &pool.fees,
+                params.lp_tokens,
+                to_a,
+            )?;
+            // Selling B for A is a B-to-A swap
+            (quote.withdraw, quote.swap.map(|swap| (!to_a, swap)), quote.amount_a, quote.amount_b)
+        }
+    };
+
+    // Both the minimums locked in on the base layer and the ones passed here have to hold
     require!(
-        amount_a_to_withdraw >= params.min_amount_a,
+        amount_a_to_withdraw >= params.min_amount_a.max(withdraw_recept.min_amount_a),
         ErrorCode::SlippageExceeded
     );
     require!(
-        amount_b_to_withdraw >= params.min_amount_b,
+        amount_b_to_withdraw >= params.min_amount_b.max(withdraw_recept.min_amount_b),
         ErrorCode::SlippageExceeded
     );
+    require!(
+        amount_a_to_withdraw > 0 || amount_b_to_withdraw > 0,
+        ErrorCode::InvalidAmount
+    );
 
     pool.reserve_a = pool.reserve_a
-        .checked_sub(amount_a_to_withdraw)
+        .checked_sub(pro_rata.amount_a)
         .ok_or(ErrorCode::InsufficientReserves)?;
 
     pool.reserve_b = pool.reserve_b
-        .checked_sub(amount_b_to_withdraw)
+        .checked_sub(pro_rata.amount_b)
         .ok_or(ErrorCode::InsufficientReserves)?;
 
+    // The unwanted leg goes back in as a regular swap, protocol fee included
+    if let Some((a_to_b, swap)) = swap_back {
+        apply_swap_to_reserves(pool, a_to_b, &swap)?;
+        msg!("Swapped {} back in for {} out (fee {})", swap.amount_in, swap.amount_out, swap.fee);
+    }
+
     pool.total_lp_supply = pool.total_lp_supply
         .checked_sub(params.lp_tokens)
         .ok_or(ErrorCode::MathOverflow)?;
 
     update_liquidity_provider_remove(
         liquidity_provider,
-        pool.key(),
+        &mut ctx.accounts.lp_position,
         amount_a_to_withdraw + amount_b_to_withdraw,
         params.lp_tokens
     )?;
 
+    // The burn handler pays out exactly these once the receipt is committed
+    withdraw_recept.amount_a_withdrawn = amount_a_to_withdraw;
+    withdraw_recept.amount_b_withdrawn = amount_b_to_withdraw;
+    withdraw_recept.status = ReceiptStatus::AppliedInER;
+
     pool.updated_at = Clock::get()?.unix_timestamp;
 
     msg!("Liquidity removed in ER: {} LP tokens", params.lp_tokens);
@@ -111,57 +157,37 @@ pub fn remove_liquidity_er(ctx: Context<RemoveLiquidityER>, params: RemoveLiquid
 
 fn update_liquidity_provider_remove(
     provider: &mut LiquidityProvider,
-    pool_key: Pubkey,
+    position: &mut LpPosition,
     liquidity_amount: u64,
     lp_tokens: u64,
 ) -> Result<()> {
-    let pool_index = provider
-        .liquidity_pools_info
-        .iter()
-        .position(|info| info.pool == pool_key)
-        .ok_or(ErrorCode::ProviderNotFound)?;
-
-    let pool_info = &mut provider.liquidity_pools_info[pool_index];
-
     require!(
-        pool_info.liquidity_amount >= liquidity_amount,
-        ErrorCode::InsufficientLiquidity
-    );
-    require!(
-        pool_info.lp_tokens >= lp_tokens,
+        position.lp_tokens >= lp_tokens,
         ErrorCode::InsufficientLpTokens
     );
 
-    pool_info.liquidity_amount = pool_info
-        .liquidity_amount
-        .checked_sub(liquidity_amount)
-        .ok_or(ErrorCode::MathOverflow)?;
+    let now = Clock::get()?.unix_timestamp;
+
+    // Token amounts are only a running tally: with fees earned or a single-token withdrawal
+    // a provider can take out more than they put in, so it bottoms out at zero.
+    // The LP tokens are what bounds the withdrawal.
+    position.liquidity_amount = position.liquidity_amount.saturating_sub(liquidity_amount);
 
-    pool_info.lp_tokens = pool_info
+    position.lp_tokens = position
         .lp_tokens
         .checked_sub(lp_tokens)
         .ok_or(ErrorCode::MathOverflow)?;
 
-    if pool_info.lp_tokens == 0 {
-        provider.liquidity_pools_info[pool_index] = LiquidityPoolInfo {
-            pool: Pubkey::default(),
-            token_mint: Pubkey::default(),
-            liquidity_amount: 0,
-            lp_tokens: 0,
-        };
-    }
+    position.last_updated_at = now;
 
-    provider.total_liquidity_provided = provider
-        .total_liquidity_provided
-        .checked_sub(liquidity_amount)
-        .ok_or(ErrorCode::MathOverflow)?;
+    provider.total_liquidity_provided = provider.total_liquidity_provided.saturating_sub(liquidity_amount);
 
     provider.total_lp_tokens = provider
         .total_lp_tokens
         .checked_sub(lp_tokens)
         .ok_or(ErrorCode::MathOverflow)?;
 
-    provider.latest_liquidity_provided_on = Clock::get()?.unix_timestamp;
+    provider.latest_liquidity_provided_on = now;
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,17 +1,28 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount, Transfer, Burn, burn};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{Mint, Token, TokenAccount, Transfer, FreezeAccount};
 
-use ephemeral_rollups_sdk::anchor::delegate;
+use ephemeral_rollups_sdk::anchor::{commit, delegate};
 use ephemeral_rollups_sdk::cpi::DelegateConfig;
+use ephemeral_rollups_sdk::ephem::commit_and_undelegate_accounts;
 use anchor_lang::Discriminator;
 
 use crate::error::ErrorCode;
+use crate::math::curve::{quote_withdraw, quote_withdraw_single};
+use crate::state::pool::Pool;
+use crate::constants::RECEIPT_TTL_SECONDS;
+use crate::deadline::require_valid_until;
+use crate::state::receipt::{ReceiptCounter, ReceiptStatus};
 
 #[derive(Accounts)]
+#[instruction(params: RemoveLiquidityParams)]
 pub struct RemoveLiquidityOnChain<'info> {
     #[account(mut)]
     pub provider: Signer<'info>,
 
+    /// CHECK: Loaded with `Pool::load_checked`; the pool may be delegated to the ER
+    pub pool: UncheckedAccount<'info>,
+
     pub mint_a: Account<'info, Mint>,
     pub mint_b: Account<'info, Mint>,
 
@@ -26,14 +37,13 @@ pub struct RemoveLiquidityOnChain<'info> {
         mint::authority = transfer_authority,
         mint::freeze_authority = transfer_authority,
         mint::decimals = 6,
-        seeds = [b"lp_token_mint"],
+        seeds = [b"lp_token_mint", pool.key().as_ref()],
         bump
     )]
     pub lp_mint: Account<'info, Mint>,
 
     #[account(
-        mut,
-        seeds = [b"token_account_a", mint_a.key().as_ref()],
+        seeds = [b"token_account_a", pool.key().as_ref()],
         bump,
         token::mint = mint_a,
         token::authority = transfer_authority
@@ -41,23 +51,25 @@ pub struct RemoveLiquidityOnChain<'info> {
     pub token_vault_a: Account<'info, TokenAccount>,
 
     #[account(
-        mut,
-        seeds = [b"token_account_b", mint_b.key().as_ref()],
+        seeds = [b"token_account_b", pool.key().as_ref()],
         bump,
         token::mint = mint_b,
         token::authority = transfer_authority
     )]
     pub token_vault_b: Account<'info, TokenAccount>,
 
+    // Paid into by the burn handler once the ER has priced the withdrawal, so both have to exist
     #[account(
-        mut,
+        init_if_needed,
+        payer = provider,
         associated_token::mint = mint_a,
         associated_token::authority = provider
     )]
     pub provider_token_a_ata: Account<'info, TokenAccount>,
 
     #[account(
-        mut,
+        init_if_needed,
+        payer = provider,
         associated_token::mint = mint_b,
         associated_token::authority = provider
     )]
@@ -74,12 +86,33 @@ pub struct RemoveLiquidityOnChain<'info> {
         init,
         payer = provider,
         space = 8 + WithdrawRecept::INIT_SPACE,
-        seeds = [b"withdraw_recept", provider.key().as_ref()],
+        seeds = [b"withdraw_recept", pool.key().as_ref(), provider.key().as_ref(), params.receipt_id.to_le_bytes().as_ref()],
         bump,
     )]
     pub withdraw_recept: Account<'info, WithdrawRecept>,
 
+    // Holds the LP being redeemed until the burn handler burns it or a cancellation returns it
+    #[account(
+        init,
+        payer = provider,
+        seeds = [b"withdraw_lp_escrow", withdraw_recept.key().as_ref()],
+        bump,
+        token::mint = lp_mint,
+        token::authority = transfer_authority
+    )]
+    pub withdraw_lp_escrow: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = provider,
+        space = 8 + ReceiptCounter::INIT_SPACE,
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump
+    )]
+    pub receipt_counter: Account<'info, ReceiptCounter>,
+
     pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
@@ -88,141 +121,152 @@ pub struct RemoveLiquidityOnChain<'info> {
 pub struct WithdrawRecept {
     pub pool: Pubkey,
     pub liquidity_provider: Pubkey,
+    pub mode: WithdrawMode,
     pub lp_tokens_to_burn: u64,
+    // Enforced again by the ER against the live reserves
+    pub min_amount_a: u64,
+    pub min_amount_b: u64,
+    // Zero until the ER applies the receipt; the burn handler pays exactly these out
     pub amount_a_withdrawn: u64,
     pub amount_b_withdrawn: u64,
+    pub receipt_id: u64,
+    pub created_at: i64,
+    // The ER refuses the receipt after this; it can then be undelegated and cancelled
+    pub expires_at: i64,
+    pub status: ReceiptStatus,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum WithdrawMode {
+    // Both tokens, pro rata
+    Balanced,
+    // Only token A (or B); the other leg is swapped back through the curve, paying the trade fee
+    SingleToken { to_a: bool },
 }
 
 #[derive(Clone, AnchorDeserialize, AnchorSerialize)]
 pub struct RemoveLiquidityParams {
+    pub mode: WithdrawMode,
     pub lp_tokens_to_burn: u64,
+    // For a single-token withdrawal the chosen side's minimum is the minimum out; nothing is paid on the other side
     pub min_amount_a: u64,
     pub min_amount_b: u64,
-    pub pool: Pubkey,
+    // Client-chosen, unique per pool and provider, so several withdrawals can be in flight at once
+    pub receipt_id: u64,
+    // Unix timestamp after which the withdrawal is rejected
+    pub valid_until: i64,
 }
 
 pub fn remove_liquidity_on_chain(
     ctx: Context<RemoveLiquidityOnChain>,
     params: RemoveLiquidityParams,
 ) -> Result<()> {
+    // Exits stay open on a paused or deactivated pool, only the single-token swap-back is gated
+    let pool = Pool::load_checked(&ctx.accounts.pool)?;
+    pool.require_token_accounts(
+        &ctx.accounts.mint_a.key(),
+        &ctx.accounts.mint_b.key(),
+        &ctx.accounts.token_vault_a.key(),
+        &ctx.accounts.token_vault_b.key(),
+    )?;
+    require_keys_eq!(ctx.accounts.lp_mint.key(), pool.lp_mint, ErrorCode::InvalidPoolState);
+
+    let now = require_valid_until(params.valid_until)?;
+
     // Validate inputs
     require!(params.lp_tokens_to_burn > 0, ErrorCode::InvalidAmount);
-    require!(params.min_amount_a > 0, ErrorCode::InvalidAmount);
-    require!(params.min_amount_b > 0, ErrorCode::InvalidAmount);
 
     // Check provider has enough LP tokens
-    // require!(
-    //     ctx.accounts.provider_token_lp_ata.amount >= params.lp_tokens_to_burn,
-    //     ErrorCode::InsufficientLpTokens
-    // );
-
-    // Check LP supply is not zero
-    // require!(ctx.accounts.lp_mint.supply > 0, ErrorCode::InvalidPoolState);
-
-    // Calculate withdrawal amounts based on LP token ratio
-    let vault_a_balance = ctx.accounts.token_vault_a.amount;
-    let vault_b_balance = ctx.accounts.token_vault_b.amount;
-    let total_lp_supply = ctx.accounts.lp_mint.supply;
-
-    require!(vault_a_balance > 0 && vault_b_balance > 0, ErrorCode::InsufficientFunds);
-
-    let amount_a_to_withdraw = (params.lp_tokens_to_burn as u128)
-        .checked_mul(vault_a_balance as u128)
-        .ok_or(ErrorCode::MathOverflow)?
-        .checked_div(total_lp_supply as u128)
-        .ok_or(ErrorCode::MathOverflow)? as u64;
-
-    let amount_b_to_withdraw = (params.lp_tokens_to_burn as u128)
-        .checked_mul(vault_b_balance as u128)
-        .ok_or(ErrorCode::MathOverflow)?
-        .checked_div(total_lp_supply as u128)
-        .ok_or(ErrorCode::MathOverflow)? as u64;
-
-    // // Check slippage protection
-    // require!(
-    //     amount_a_to_withdraw >= params.min_amount_a,
-    //     ErrorCode::SlippageExceeded
-    // );
-    // require!(
-    //     amount_b_to_withdraw >= params.min_amount_b,
-    //     ErrorCode::SlippageExceeded
-    // );
-
-    // Verify vaults have enough tokens
     require!(
-        vault_a_balance >= amount_a_to_withdraw,
-        ErrorCode::InsufficientFunds
-    );
-    require!(
-        vault_b_balance >= amount_b_to_withdraw,
-        ErrorCode::InsufficientFunds
+        ctx.accounts.provider_token_lp_ata.amount >= params.lp_tokens_to_burn,
+        ErrorCode::InsufficientLpTokens
     );
 
-    // Step 1: Burn LP tokens from provider
-    let cpi_accounts_burn = Burn {
-        mint: ctx.accounts.lp_mint.to_account_info(),
+    // Check LP supply is not zero, on the mint and in the committed pool
+    require!(ctx.accounts.lp_mint.supply > 0, ErrorCode::InvalidPoolState);
+    require!(pool.total_lp_supply > 0, ErrorCode::InvalidPoolState);
+
+    // Only an estimate against the last committed pool, so an obviously stale minimum fails here;
+    // the amounts actually paid are the ones the ER records on the receipt
+    let (estimate_a, estimate_b) = match params.mode {
+        WithdrawMode::Balanced => {
+            let quote = quote_withdraw(pool.reserve_a, pool.reserve_b, pool.total_lp_supply, params.lp_tokens_to_burn)?;
+            (quote.amount_a, quote.amount_b)
+        }
+        WithdrawMode::SingleToken { to_a } => {
+            pool.require_tradable()?;
+            let quote = quote_withdraw_single(
+                pool.reserve_a,
+                pool.reserve_b,
+                pool.total_lp_supply,
+                &pool.fees,
+                params.lp_tokens_to_burn,
+                to_a,
+            )?;
+            (quote.amount_a, quote.amount_b)
+        }
+    };
+
+    // Check slippage protection; a zero minimum opts out for that side
+    require!(estimate_a >= params.min_amount_a, ErrorCode::SlippageExceeded);
+    require!(estimate_b >= params.min_amount_b, ErrorCode::SlippageExceeded);
+    require!(estimate_a > 0 || estimate_b > 0, ErrorCode::InvalidAmount);
+
+    // Move the LP into the receipt's escrow: nothing is burned or paid out until the ER has applied it
+    let cpi_accounts_lock = Transfer {
         from: ctx.accounts.provider_token_lp_ata.to_account_info(),
+        to: ctx.accounts.withdraw_lp_escrow.to_account_info(),
         authority: ctx.accounts.provider.to_account_info(),
     };
 
-    let cpi_ctx_burn = CpiContext::new(
+    let cpi_ctx_lock = CpiContext::new(
         ctx.accounts.token_program.to_account_info(),
-        cpi_accounts_burn,
+        cpi_accounts_lock,
     );
-    burn(cpi_ctx_burn, params.lp_tokens_to_burn)?;
+    anchor_spl::token::transfer(cpi_ctx_lock, params.lp_tokens_to_burn)?;
 
-    msg!("Burned {} LP tokens", params.lp_tokens_to_burn);
-
-    // Step 2: Transfer tokens from vaults to provider
+    // Frozen with the LP mint's freeze authority, so only the settle or cancel paths, which thaw
+    // it first, can move LP the ER may already have counted as redeemed
     let signer_seeds: &[&[&[u8]]] = &[&[
         b"transfer_authority".as_ref(),
         &[ctx.bumps.transfer_authority],
     ]];
 
-    // Transfer Token A
-    let cpi_accounts_token_a = Transfer {
-        from: ctx.accounts.token_vault_a.to_account_info(),
-        to: ctx.accounts.provider_token_a_ata.to_account_info(),
+    let cpi_accounts_freeze = FreezeAccount {
+        account: ctx.accounts.withdraw_lp_escrow.to_account_info(),
+        mint: ctx.accounts.lp_mint.to_account_info(),
         authority: ctx.accounts.transfer_authority.to_account_info(),
     };
 
-    let cpi_ctx_a = CpiContext::new_with_signer(
+    let cpi_ctx_freeze = CpiContext::new_with_signer(
         ctx.accounts.token_program.to_account_info(),
-        cpi_accounts_token_a,
+        cpi_accounts_freeze,
         signer_seeds,
     );
-    anchor_spl::token::transfer(cpi_ctx_a, amount_a_to_withdraw)?;
+    anchor_spl::token::freeze_account(cpi_ctx_freeze)?;
 
-    msg!("Transferred {} of token A to provider", amount_a_to_withdraw);
+    msg!("Locked {} LP tokens in the withdraw escrow", params.lp_tokens_to_burn);
 
-    // Transfer Token B
-    let cpi_accounts_token_b = Transfer {
-        from: ctx.accounts.token_vault_b.to_account_info(),
-        to: ctx.accounts.provider_token_b_ata.to_account_info(),
-        authority: ctx.accounts.transfer_authority.to_account_info(),
-    };
+    ctx.accounts.receipt_counter.open_receipt(ctx.accounts.pool.key(), ctx.bumps.receipt_counter)?;
 
-    let cpi_ctx_b = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        cpi_accounts_token_b,
-        signer_seeds,
-    );
-    anchor_spl::token::transfer(cpi_ctx_b, amount_b_to_withdraw)?;
-
-    msg!("Transferred {} of token B to provider", amount_b_to_withdraw);
-
-    // Step 3: Create withdraw receipt
     let withdraw_receipt = &mut ctx.accounts.withdraw_recept;
-    withdraw_receipt.pool = params.pool;
+    withdraw_receipt.pool = ctx.accounts.pool.key();
     withdraw_receipt.liquidity_provider = ctx.accounts.provider.key();
+    withdraw_receipt.mode = params.mode;
     withdraw_receipt.lp_tokens_to_burn = params.lp_tokens_to_burn;
-    withdraw_receipt.amount_a_withdrawn = amount_a_to_withdraw;
-    withdraw_receipt.amount_b_withdrawn = amount_b_to_withdraw;
+    withdraw_receipt.min_amount_a = params.min_amount_a;
+    withdraw_receipt.min_amount_b = params.min_amount_b;
+    withdraw_receipt.amount_a_withdrawn = 0;
+    withdraw_receipt.amount_b_withdrawn = 0;
+    withdraw_receipt.receipt_id = params.receipt_id;
+    withdraw_receipt.created_at = now;
+    withdraw_receipt.expires_at = std::cmp::min(now + RECEIPT_TTL_SECONDS, params.valid_until);
+    withdraw_receipt.status = ReceiptStatus::Created;
+    withdraw_receipt.bump = ctx.bumps.withdraw_recept;
 
     msg!("Created withdraw receipt account");
-    msg!("LP tokens burned: {}", params.lp_tokens_to_burn);
-    msg!("Token A withdrawn: {}", amount_a_to_withdraw);
-    msg!("Token B withdrawn: {}", amount_b_to_withdraw);
+    msg!("Estimated withdrawal: A={}, B={}", estimate_a, estimate_b);
 
     Ok(())
 }
@@ -236,8 +280,13 @@ pub struct DelegateRemoveLiquidityReceipt<'info> {
     #[account(
         mut,
         del,
-        seeds = [b"withdraw_recept", provider.key().as_ref()],
-        bump,
+        seeds = [
+            b"withdraw_recept",
+            withdraw_recept.pool.as_ref(),
+            provider.key().as_ref(),
+            withdraw_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = withdraw_recept.bump,
     )]
     pub withdraw_recept: Account<'info, WithdrawRecept>,
 }
@@ -252,8 +301,19 @@ pub fn delegate_remove_liquidity_receipt(
         validator: Some(validator_key),
     };
 
+    require!(
+        ctx.accounts.withdraw_recept.status == ReceiptStatus::Created,
+        ErrorCode::InvalidReceiptStatus
+    );
+
+    // Delegation copies the account data, so the new status has to be written first
+    ctx.accounts.withdraw_recept.status = ReceiptStatus::Delegated;
+    ctx.accounts.withdraw_recept.exit(&crate::ID)?;
+
     let provider = ctx.accounts.provider.key();
-    let seeds = &[b"withdraw_recept", provider.as_ref()];
+    let pool = ctx.accounts.withdraw_recept.pool;
+    let receipt_id = ctx.accounts.withdraw_recept.receipt_id.to_le_bytes();
+    let seeds = &[b"withdraw_recept".as_ref(), pool.as_ref(), provider.as_ref(), receipt_id.as_ref()];
 
     ctx.accounts.delegate_withdraw_recept(
         &ctx.accounts.provider,
@@ -265,4 +325,49 @@ pub fn delegate_remove_liquidity_receipt(
     msg!("Receipt: {:?}", ctx.accounts.withdraw_recept);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+pub fn commit_and_undelegate_withdraw_receipt(ctx: Context<CommitAndUndelegateWithdrawReceipt>) -> Result<()> {
+    ctx.accounts.withdraw_recept.status.require_undelegatable(
+        ctx.accounts.withdraw_recept.expires_at,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    commit_and_undelegate_accounts(
+        &ctx.accounts.provider,
+        vec![
+            &ctx.accounts.withdraw_recept.to_account_info(),
+        ],
+        &ctx.accounts.magic_context,
+        &ctx.accounts.magic_program
+    )?;
+
+    Ok(())
+
+}
+
+#[commit]
+#[derive(Accounts)]
+pub struct CommitAndUndelegateWithdrawReceipt<'info> {
+    #[account(mut)]
+    pub provider: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"withdraw_recept",
+            withdraw_recept.pool.as_ref(),
+            provider.key().as_ref(),
+            withdraw_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = withdraw_recept.bump,
+    )]
+    pub withdraw_recept: Account<'info, WithdrawRecept>,
+
+    /// CHECK: Magic context account
+    #[account(mut)]
+    pub magic_context: UncheckedAccount<'info>,
+
+    /// CHECK: Magic program
+    pub magic_program: UncheckedAccount<'info>,
+}
@@ -0,0 +1,165 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount, Transfer};
+
+use crate::state::pool::Pool;
+use crate::error::ErrorCode;
+use crate::deadline::require_valid_until;
+use crate::math::curve::{quote_swap, SwapMode, SwapQuote};
+
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", pool.name.as_bytes()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    /// CHECK: Transfer authority PDA
+    #[account(
+        seeds = [b"transfer_authority"],
+        bump,
+    )]
+    pub transfer_authority: UncheckedAccount<'info>,
+
+    #[account(address = pool.token_a)]
+    pub mint_a: Account<'info, Mint>,
+
+    #[account(address = pool.token_b)]
+    pub mint_b: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump = pool.token_a_vault_bump,
+        token::mint = mint_a,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump = pool.token_b_vault_bump,
+        token::mint = mint_b,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = user
+    )]
+    pub user_token_a_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = user
+    )]
+    pub user_token_b_ata: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Clone, AnchorDeserialize, AnchorSerialize)]
+pub struct SwapParams {
+    pub a_to_b: bool,
+    pub mode: SwapMode,
+    pub valid_until: i64,
+}
+
+pub fn swap(ctx: Context<Swap>, params: SwapParams) -> Result<()> {
+
+    let pool = &mut ctx.accounts.pool;
+
+    pool.require_tradable()?;
+
+    let now = require_valid_until(params.valid_until)?;
+
+    let (reserve_in, reserve_out) = if params.a_to_b {
+        (pool.reserve_a, pool.reserve_b)
+    } else {
+        (pool.reserve_b, pool.reserve_a)
+    };
+
+    let quote = quote_swap(reserve_in, reserve_out, &pool.fees, params.mode)?;
+
+    let (user_source, vault_destination, vault_source, user_destination) = if params.a_to_b {
+        (&ctx.accounts.user_token_a_ata, &ctx.accounts.token_vault_a, &ctx.accounts.token_vault_b, &ctx.accounts.user_token_b_ata)
+    } else {
+        (&ctx.accounts.user_token_b_ata, &ctx.accounts.token_vault_b, &ctx.accounts.token_vault_a, &ctx.accounts.user_token_a_ata)
+    };
+
+    require!(user_source.amount >= quote.amount_in, ErrorCode::InsufficientBalance);
+    require!(vault_source.amount >= quote.amount_out, ErrorCode::InsufficientReserves);
+
+    let cpi_accounts_in = Transfer {
+        from: user_source.to_account_info(),
+        to: vault_destination.to_account_info(),
+        authority: ctx.accounts.user.to_account_info(),
+    };
+
+    let cpi_ctx_in = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts_in);
+    anchor_spl::token::transfer(cpi_ctx_in, quote.amount_in)?;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"transfer_authority".as_ref(),
+        &[ctx.bumps.transfer_authority],
+    ]];
+
+    let cpi_accounts_out = Transfer {
+        from: vault_source.to_account_info(),
+        to: user_destination.to_account_info(),
+        authority: ctx.accounts.transfer_authority.to_account_info(),
+    };
+
+    let cpi_ctx_out = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        cpi_accounts_out,
+        signer_seeds,
+    );
+    anchor_spl::token::transfer(cpi_ctx_out, quote.amount_out)?;
+
+    apply_swap_to_reserves(pool, params.a_to_b, &quote)?;
+
+    pool.updated_at = now;
+
+    msg!("Swapped {} in for {} out (fee {}, protocol {})", quote.amount_in, quote.amount_out, quote.fee, quote.protocol_fee);
+    msg!("New reserves: A={}, B={}", pool.reserve_a, pool.reserve_b);
+
+    Ok(())
+}
+
+/// Moves the pool's virtual reserves by a quoted swap. The input minus the protocol fee stays with the LPs;
+/// the protocol fee stays in the input vault and is tracked as owed until it is claimed.
+pub fn apply_swap_to_reserves(pool: &mut Pool, a_to_b: bool, quote: &SwapQuote) -> Result<()> {
+    let (reserve_in, reserve_out, protocol_fees_owed) = if a_to_b {
+        (&mut pool.reserve_a, &mut pool.reserve_b, &mut pool.protocol_fees_owed_a)
+    } else {
+        (&mut pool.reserve_b, &mut pool.reserve_a, &mut pool.protocol_fees_owed_b)
+    };
+
+    let lp_amount_in = quote
+        .amount_in
+        .checked_sub(quote.protocol_fee)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    *reserve_in = reserve_in
+        .checked_add(lp_amount_in)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    *protocol_fees_owed = protocol_fees_owed
+        .checked_add(quote.protocol_fee)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    *reserve_out = reserve_out
+        .checked_sub(quote.amount_out)
+        .ok_or(ErrorCode::InsufficientReserves)?;
+
+    Ok(())
+}
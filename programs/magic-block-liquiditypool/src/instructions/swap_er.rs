@@ -0,0 +1,248 @@
+//Pure state change in ER only
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+use ephemeral_rollups_sdk::anchor::commit;
+use ephemeral_rollups_sdk::ephem::{MagicInstructionBuilder, MagicAction, CallHandler, CommitType, CommitAndUndelegate, UndelegateType};
+use ephemeral_rollups_sdk::{ActionArgs, ShortAccountMeta};
+
+use crate::state::pool::Pool;
+use crate::error::ErrorCode;
+use crate::math::curve::quote_swap;
+use crate::instructions::swap::apply_swap_to_reserves;
+use crate::instructions::swap_on_chain::SwapRecept;
+use crate::state::receipt::ReceiptStatus;
+
+#[derive(Accounts)]
+pub struct SwapER<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", pool.name.as_bytes()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"swap_recept",
+            pool.key().as_ref(),
+            user.key().as_ref(),
+            swap_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = swap_recept.bump,
+    )]
+    pub swap_recept: Account<'info, SwapRecept>,
+}
+
+pub fn swap_er(ctx: Context<SwapER>) -> Result<()> {
+
+    let pool = &mut ctx.accounts.pool;
+    let swap_recept = &mut ctx.accounts.swap_recept;
+
+    pool.require_tradable()?;
+
+    require!(swap_recept.pool == pool.key(), ErrorCode::InvalidPoolState);
+    require!(
+        swap_recept.mint_a == pool.token_a && swap_recept.mint_b == pool.token_b,
+        ErrorCode::InvalidPoolState
+    );
+    require!(swap_recept.status == ReceiptStatus::Delegated, ErrorCode::InvalidReceiptStatus);
+
+    let clock = Clock::get()?;
+    require!(clock.unix_timestamp <= swap_recept.expires_at, ErrorCode::ReceiptExpired);
+
+    msg!("Executing swap in ER (state only)...");
+
+    let (reserve_in, reserve_out) = if swap_recept.a_to_b {
+        (pool.reserve_a, pool.reserve_b)
+    } else {
+        (pool.reserve_b, pool.reserve_a)
+    };
+
+    let quote = quote_swap(reserve_in, reserve_out, &pool.fees, swap_recept.mode)?;
+
+    require!(quote.amount_in <= swap_recept.amount_escrowed, ErrorCode::SlippageExceeded);
+
+    apply_swap_to_reserves(pool, swap_recept.a_to_b, &quote)?;
+
+    swap_recept.amount_in = quote.amount_in;
+    swap_recept.amount_out = quote.amount_out;
+    swap_recept.fee = quote.fee;
+    swap_recept.status = ReceiptStatus::AppliedInER;
+
+    pool.updated_at = clock.unix_timestamp;
+
+    msg!("Swapped in ER: {} in for {} out (fee {}, protocol {})", quote.amount_in, quote.amount_out, quote.fee, quote.protocol_fee);
+    msg!("New reserves: A={}, B={}", pool.reserve_a, pool.reserve_b);
+
+    Ok(())
+}
+
+#[commit]
+#[derive(Accounts)]
+pub struct CommitAndSettleSwap<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"swap_recept",
+            swap_recept.pool.as_ref(),
+            user.key().as_ref(),
+            swap_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = swap_recept.bump,
+    )]
+    pub swap_recept: Account<'info, SwapRecept>,
+
+    /// CHECK: Bound to the receipt
+    #[account(address = swap_recept.pool)]
+    pub pool: UncheckedAccount<'info>,
+
+    /// CHECK: Transfer authority PDA
+    #[account(
+        seeds = [b"transfer_authority"],
+        bump,
+    )]
+    pub transfer_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the settle handler on the base layer
+    pub mint_a: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the settle handler on the base layer
+    pub mint_b: UncheckedAccount<'info>,
+
+    /// CHECK: Pool vault, resolved from the pool key
+    #[account(
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump
+    )]
+    pub token_vault_a: UncheckedAccount<'info>,
+
+    /// CHECK: Pool vault, resolved from the pool key
+    #[account(
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump
+    )]
+    pub token_vault_b: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the settle handler on the base layer
+    pub user_token_a_ata: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the settle handler on the base layer
+    pub user_token_b_ata: UncheckedAccount<'info>,
+
+    /// CHECK: The pool's receipt counter, decremented by the settle handler on the base layer
+    #[account(
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump
+    )]
+    pub receipt_counter: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// CHECK: Magic context account
+    #[account(mut)]
+    pub magic_context: UncheckedAccount<'info>,
+
+    /// CHECK: Magic program
+    pub magic_program: UncheckedAccount<'info>,
+}
+
+pub fn commit_and_settle_swap(ctx: Context<CommitAndSettleSwap>) -> Result<()> {
+
+    require!(
+        ctx.accounts.swap_recept.status == ReceiptStatus::AppliedInER,
+        ErrorCode::InvalidReceiptStatus
+    );
+
+    let settle_instruction_data = anchor_lang::InstructionData::data(
+        &crate::instruction::ProcessSettleSwap {}
+    );
+
+    let action_args = ActionArgs {
+        escrow_index: crate::action_guard::ACTION_ESCROW_INDEX,
+        data: settle_instruction_data,
+    };
+
+    let accounts = vec![
+        ShortAccountMeta {
+            pubkey: ctx.accounts.user.key(),
+            is_writable: true,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.pool.key(),
+            is_writable: false,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.transfer_authority.key(),
+            is_writable: false,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.mint_a.key(),
+            is_writable: false,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.mint_b.key(),
+            is_writable: false,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.token_vault_a.key(),
+            is_writable: true,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.token_vault_b.key(),
+            is_writable: true,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.user_token_a_ata.key(),
+            is_writable: true,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.user_token_b_ata.key(),
+            is_writable: true,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.swap_recept.key(),
+            is_writable: true,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.receipt_counter.key(),
+            is_writable: true,
+        },
+        ShortAccountMeta {
+            pubkey: ctx.accounts.token_program.key(),
+            is_writable: false,
+        },
+    ];
+
+    let settle_handler = CallHandler {
+        args: action_args,
+        compute_units: 200_000,
+        escrow_authority: ctx.accounts.user.to_account_info(),
+        destination_program: crate::ID,
+        accounts,
+    };
+
+    MagicInstructionBuilder {
+        payer: ctx.accounts.user.to_account_info(),
+        magic_context: ctx.accounts.magic_context.to_account_info(),
+        magic_program: ctx.accounts.magic_program.to_account_info(),
+        magic_action: MagicAction::CommitAndUndelegate(
+            CommitAndUndelegate {
+                commit_type: CommitType::Standalone(
+                    vec![ctx.accounts.swap_recept.to_account_info()]
+                ),
+                undelegate_type: UndelegateType::WithHandler(vec![settle_handler])
+            }
+        )
+    }.build_and_invoke()?;
+
+    Ok(())
+}
@@ -0,0 +1,472 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount, Transfer};
+
+use ephemeral_rollups_sdk::anchor::{commit, delegate};
+use ephemeral_rollups_sdk::cpi::DelegateConfig;
+use ephemeral_rollups_sdk::ephem::commit_and_undelegate_accounts;
+
+use crate::error::ErrorCode;
+use crate::constants::RECEIPT_TTL_SECONDS;
+use crate::deadline::require_valid_until;
+use crate::state::pool::Pool;
+use crate::state::receipt::{ReceiptCounter, ReceiptStatus};
+use crate::action_guard::verify_action_escrow;
+use crate::math::curve::SwapMode;
+
+#[derive(Accounts)]
+#[instruction(params: SwapOnChainParams)]
+pub struct SwapOnChain<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: Loaded with `Pool::load_checked`; the pool may be delegated to the ER
+    pub pool: UncheckedAccount<'info>,
+
+    pub mint_a: Account<'info, Mint>,
+    pub mint_b: Account<'info, Mint>,
+
+    /// CHECK: Transfer authority PDA
+    #[account(
+        seeds = [b"transfer_authority"],
+        bump,
+    )]
+    pub transfer_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump,
+        token::mint = mint_a,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump,
+        token::mint = mint_b,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = user
+    )]
+    pub user_token_a_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = user
+    )]
+    pub user_token_b_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + SwapRecept::INIT_SPACE,
+        seeds = [b"swap_recept", pool.key().as_ref(), user.key().as_ref(), params.receipt_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub swap_recept: Account<'info, SwapRecept>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + ReceiptCounter::INIT_SPACE,
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump
+    )]
+    pub receipt_counter: Account<'info, ReceiptCounter>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Clone, AnchorDeserialize, AnchorSerialize)]
+pub struct SwapOnChainParams {
+    pub a_to_b: bool,
+    pub mode: SwapMode,
+    pub valid_until: i64,
+    // Client-chosen, unique per pool and user, so several swaps can be in flight at once
+    pub receipt_id: u64,
+}
+
+#[account]
+#[derive(Debug, InitSpace)]
+pub struct SwapRecept {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub a_to_b: bool,
+    pub mode: SwapMode,
+    pub amount_escrowed: u64,
+    // Filled in by the ER once the swap has been priced against the virtual reserves
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    pub receipt_id: u64,
+    pub created_at: i64,
+    // The ER refuses the receipt after this; it can then be undelegated and cancelled
+    pub expires_at: i64,
+    pub status: ReceiptStatus,
+    pub bump: u8,
+}
+
+pub fn swap_on_chain(ctx: Context<SwapOnChain>, params: SwapOnChainParams) -> Result<()> {
+
+    let pool = Pool::load_checked(&ctx.accounts.pool)?;
+    pool.require_tradable()?;
+    pool.require_token_accounts(
+        &ctx.accounts.mint_a.key(),
+        &ctx.accounts.mint_b.key(),
+        &ctx.accounts.token_vault_a.key(),
+        &ctx.accounts.token_vault_b.key(),
+    )?;
+
+    let now = require_valid_until(params.valid_until)?;
+
+    // Exact-out swaps escrow the maximum input; the unused part is refunded on settlement
+    let amount_escrowed = match params.mode {
+        SwapMode::ExactIn { amount_in, .. } => amount_in,
+        SwapMode::ExactOut { amount_out, max_amount_in } => {
+            require!(amount_out > 0, ErrorCode::InvalidAmount);
+            max_amount_in
+        }
+    };
+
+    require!(amount_escrowed > 0, ErrorCode::InvalidAmount);
+
+    let (user_source, vault_destination) = if params.a_to_b {
+        (&ctx.accounts.user_token_a_ata, &ctx.accounts.token_vault_a)
+    } else {
+        (&ctx.accounts.user_token_b_ata, &ctx.accounts.token_vault_b)
+    };
+
+    require!(user_source.amount >= amount_escrowed, ErrorCode::InsufficientBalance);
+
+    let cpi_accounts = Transfer {
+        from: user_source.to_account_info(),
+        to: vault_destination.to_account_info(),
+        authority: ctx.accounts.user.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+    anchor_spl::token::transfer(cpi_ctx, amount_escrowed)?;
+
+    msg!("Escrowed {} input tokens in the vault", amount_escrowed);
+
+    ctx.accounts.receipt_counter.open_receipt(ctx.accounts.pool.key(), ctx.bumps.receipt_counter)?;
+
+    let swap_recept = &mut ctx.accounts.swap_recept;
+    swap_recept.pool = ctx.accounts.pool.key();
+    swap_recept.user = ctx.accounts.user.key();
+    swap_recept.mint_a = ctx.accounts.mint_a.key();
+    swap_recept.mint_b = ctx.accounts.mint_b.key();
+    swap_recept.a_to_b = params.a_to_b;
+    swap_recept.mode = params.mode;
+    swap_recept.amount_escrowed = amount_escrowed;
+    swap_recept.amount_in = 0;
+    swap_recept.amount_out = 0;
+    swap_recept.fee = 0;
+    swap_recept.receipt_id = params.receipt_id;
+    swap_recept.created_at = now;
+    swap_recept.expires_at = std::cmp::min(now + RECEIPT_TTL_SECONDS, params.valid_until);
+    swap_recept.status = ReceiptStatus::Created;
+    swap_recept.bump = ctx.bumps.swap_recept;
+
+    msg!("Swap Recept created successfully!");
+
+    Ok(())
+}
+
+#[delegate]
+#[derive(Accounts)]
+pub struct DelegateSwapReceipt<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        del,
+        seeds = [
+            b"swap_recept",
+            swap_recept.pool.as_ref(),
+            user.key().as_ref(),
+            swap_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = swap_recept.bump
+    )]
+    pub swap_recept: Account<'info, SwapRecept>,
+}
+
+pub fn delegate_swap_receipt(
+    ctx: Context<DelegateSwapReceipt>,
+    commit_frequency: u32,
+    validator_key: Pubkey,
+) -> Result<()> {
+    let delegate_config = DelegateConfig {
+        commit_frequency_ms: commit_frequency,
+        validator: Some(validator_key),
+    };
+
+    require!(
+        ctx.accounts.swap_recept.status == ReceiptStatus::Created,
+        ErrorCode::InvalidReceiptStatus
+    );
+
+    // Delegation copies the account data, so the new status has to be written first
+    ctx.accounts.swap_recept.status = ReceiptStatus::Delegated;
+    ctx.accounts.swap_recept.exit(&crate::ID)?;
+
+    let user = ctx.accounts.user.key();
+    let pool = ctx.accounts.swap_recept.pool;
+    let receipt_id = ctx.accounts.swap_recept.receipt_id.to_le_bytes();
+    let seeds = &[b"swap_recept".as_ref(), pool.as_ref(), user.as_ref(), receipt_id.as_ref()];
+
+    ctx.accounts.delegate_swap_recept(
+        &ctx.accounts.user,
+        seeds,
+        delegate_config,
+    )?;
+
+    msg!("Swap receipt delegated successfully!");
+    msg!("Receipt : {:?}", ctx.accounts.swap_recept);
+
+    Ok(())
+}
+
+// Brings back a receipt the ER never executed, so it can be cancelled on the base layer
+pub fn commit_and_undelegate_swap_receipt(ctx: Context<CommitAndUndelegateSwapReceipt>) -> Result<()> {
+    ctx.accounts.swap_recept.status.require_undelegatable(
+        ctx.accounts.swap_recept.expires_at,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    commit_and_undelegate_accounts(
+        &ctx.accounts.user,
+        vec![
+            &ctx.accounts.swap_recept.to_account_info(),
+        ],
+        &ctx.accounts.magic_context,
+        &ctx.accounts.magic_program
+    )?;
+
+    Ok(())
+}
+
+#[commit]
+#[derive(Accounts)]
+pub struct CommitAndUndelegateSwapReceipt<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"swap_recept",
+            swap_recept.pool.as_ref(),
+            user.key().as_ref(),
+            swap_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = swap_recept.bump,
+    )]
+    pub swap_recept: Account<'info, SwapRecept>,
+
+    /// CHECK: Magic context account
+    #[account(mut)]
+    pub magic_context: UncheckedAccount<'info>,
+
+    /// CHECK: Magic program
+    pub magic_program: UncheckedAccount<'info>,
+}
+
+// Everything settling a swap receipt touches, shared by the settle handler and its retry
+#[derive(Accounts)]
+pub struct SwapSettlement<'info> {
+    /// CHECK: Receipt owner, bound through the receipt seeds
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+
+    /// CHECK: Only its key is used to derive the pool-owned PDAs
+    #[account(address = swap_recept.pool)]
+    pub pool: UncheckedAccount<'info>,
+
+    /// CHECK: Transfer authority PDA
+    #[account(
+        seeds = [b"transfer_authority"],
+        bump,
+    )]
+    pub transfer_authority: UncheckedAccount<'info>,
+
+    #[account(address = swap_recept.mint_a)]
+    pub mint_a: Account<'info, Mint>,
+
+    #[account(address = swap_recept.mint_b)]
+    pub mint_b: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump,
+        token::mint = mint_a,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump,
+        token::mint = mint_b,
+        token::authority = transfer_authority
+    )]
+    pub token_vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = user
+    )]
+    pub user_token_a_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = user
+    )]
+    pub user_token_b_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"swap_recept",
+            pool.key().as_ref(),
+            user.key().as_ref(),
+            swap_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = swap_recept.bump,
+        close = user
+    )]
+    pub swap_recept: Account<'info, SwapRecept>,
+
+    #[account(
+        mut,
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump = receipt_counter.bump
+    )]
+    pub receipt_counter: Account<'info, ReceiptCounter>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SettleSwap<'info> {
+    pub settle: SwapSettlement<'info>,
+
+    /// CHECK: the correct pda - this will be moved to the end in the future, meaning you can omit this unless needed
+    pub escrow: UncheckedAccount<'info>,
+    /// CHECK: the correct pda - this will be moved to the end in the future, meaning you can omit this unless needed
+    pub escrow_auth: UncheckedAccount<'info>,
+}
+
+/// Runs on the base layer after the swap receipt is committed and undelegated:
+/// pays the output priced in the ER and refunds whatever part of the escrowed input was not used.
+pub fn settle_swap(ctx: Context<SettleSwap>) -> Result<()> {
+    verify_action_escrow(
+        &ctx.accounts.escrow,
+        &ctx.accounts.escrow_auth,
+        &ctx.accounts.settle.user.key(),
+    )?;
+
+    ctx.accounts.settle.settle(ctx.bumps.settle.transfer_authority)
+}
+
+#[derive(Accounts)]
+pub struct RetrySettleSwap<'info> {
+    pub settle: SwapSettlement<'info>,
+}
+
+/// Base layer: settles a receipt the ER executed but whose settle handler failed after it was
+/// undelegated, exactly as the handler would have. The user signs it once the receipt expired.
+pub fn retry_settle_swap(ctx: Context<RetrySettleSwap>) -> Result<()> {
+    let settle = &mut ctx.accounts.settle;
+    require!(settle.user.is_signer, ErrorCode::Unauthorized);
+
+    settle.swap_recept.status.require_retryable(
+        settle.swap_recept.expires_at,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    settle.settle(ctx.bumps.settle.transfer_authority)
+}
+
+impl<'info> SwapSettlement<'info> {
+    fn settle(&mut self, transfer_authority_bump: u8) -> Result<()> {
+        self.receipt_counter.close_receipt()?;
+
+        require!(self.swap_recept.status == ReceiptStatus::AppliedInER, ErrorCode::InvalidReceiptStatus);
+        self.swap_recept.status = ReceiptStatus::Settled;
+
+        let swap_recept = &self.swap_recept;
+
+        let amount_out = swap_recept.amount_out;
+        let refund = swap_recept
+            .amount_escrowed
+            .checked_sub(swap_recept.amount_in)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let (vault_in, user_in, vault_out, user_out) = if swap_recept.a_to_b {
+            (&self.token_vault_a, &self.user_token_a_ata, &self.token_vault_b, &self.user_token_b_ata)
+        } else {
+            (&self.token_vault_b, &self.user_token_b_ata, &self.token_vault_a, &self.user_token_a_ata)
+        };
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"transfer_authority".as_ref(),
+            &[transfer_authority_bump],
+        ]];
+
+        if amount_out > 0 {
+            let cpi_accounts = Transfer {
+                from: vault_out.to_account_info(),
+                to: user_out.to_account_info(),
+                authority: self.transfer_authority.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+            anchor_spl::token::transfer(cpi_ctx, amount_out)?;
+
+            msg!("Paid out {} tokens to user", amount_out);
+        }
+
+        if refund > 0 {
+            let cpi_accounts = Transfer {
+                from: vault_in.to_account_info(),
+                to: user_in.to_account_info(),
+                authority: self.transfer_authority.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+            anchor_spl::token::transfer(cpi_ctx, refund)?;
+
+            msg!("Refunded {} unused input tokens to user", refund);
+        }
+
+        msg!("Swap receipt settled and closed!");
+
+        Ok(())
+    }
+}
@@ -6,16 +6,20 @@ use ephemeral_rollups_sdk::ephem::{MagicInstructionBuilder, MagicAction, CallHan
 use ephemeral_rollups_sdk::{ActionArgs, ShortAccountMeta};
 use anchor_lang::Discriminator;
 
+pub mod action_guard;
 pub mod constants;
+pub mod deadline;
 pub mod error;
+pub mod events;
 pub mod instructions;
+pub mod math;
 pub mod state;
 
 pub use constants::*;
 pub use instructions::*;
 pub use state::*;
 
-use state::{pool::Pool, liquidity_provider::LiquidityProvider};
+use state::{pool::Pool, liquidity_provider::LiquidityProvider, lp_position::LpPosition, receipt::ReceiptStatus};
 
 declare_id!("7VHw8JmcxmtkuF4pftysUeePFhEKMPe6M9LnzjNwYkh3");
 
@@ -29,6 +33,10 @@ pub mod magic_block_liquiditypool {
         initialize::handler(ctx)
     }
 
+    pub fn process_initialize_factory(ctx: Context<InitializeFactory>) -> Result<()> {
+        instructions::init_factory::initialize_factory(ctx)
+    }
+
     pub fn process_initialize_pool(ctx: Context<InitializeLiquidityPool>, params: AddPoolParams) -> Result<()> {
         instructions::init_pool::initialize_liquidity_pool(ctx, params)
     }
@@ -37,6 +45,54 @@ pub mod magic_block_liquiditypool {
         instructions::delegate_pool_er::delegate_pool(ctx, commit_frequency, validator_key)
     }
 
+    pub fn process_commit_pool(ctx: Context<CommitPool>) -> Result<()> {
+        instructions::commit_pool::commit_pool(ctx)
+    }
+
+    pub fn process_commit_and_undelegate_pool(ctx: Context<CommitPool>) -> Result<()> {
+        instructions::commit_pool::commit_and_undelegate_pool(ctx)
+    }
+
+    pub fn process_pause_pool(ctx: Context<PoolAdmin>) -> Result<()> {
+        instructions::pool_admin::pause_pool(ctx)
+    }
+
+    pub fn process_resume_pool(ctx: Context<PoolAdmin>) -> Result<()> {
+        instructions::pool_admin::resume_pool(ctx)
+    }
+
+    pub fn process_deactivate_pool(ctx: Context<PoolAdmin>) -> Result<()> {
+        instructions::pool_admin::deactivate_pool(ctx)
+    }
+
+    pub fn process_update_pool_fees(ctx: Context<PoolAdmin>, params: UpdatePoolFeesParams) -> Result<()> {
+        instructions::pool_admin::update_pool_fees(ctx, params)
+    }
+
+    pub fn process_set_fee_recipient(ctx: Context<PoolAdmin>, new_fee_recipient: Pubkey) -> Result<()> {
+        instructions::pool_admin::set_fee_recipient(ctx, new_fee_recipient)
+    }
+
+    pub fn process_propose_admin(ctx: Context<PoolAdmin>, new_admin: Pubkey) -> Result<()> {
+        instructions::pool_admin::propose_admin(ctx, new_admin)
+    }
+
+    pub fn process_accept_admin(ctx: Context<AcceptPoolAdmin>) -> Result<()> {
+        instructions::pool_admin::accept_admin(ctx)
+    }
+
+    pub fn process_claim_protocol_fees(ctx: Context<ClaimProtocolFees>) -> Result<()> {
+        instructions::claim_protocol_fees::claim_protocol_fees(ctx)
+    }
+
+    pub fn process_reconcile_pool<'info>(ctx: Context<'_, '_, 'info, 'info, ReconcilePool<'info>>) -> Result<()> {
+        instructions::reconcile_pool::reconcile_pool(ctx)
+    }
+
+    pub fn process_check_pool_invariants<'info>(ctx: Context<'_, '_, 'info, 'info, CheckPoolInvariants<'info>>) -> Result<()> {
+        instructions::reconcile_pool::check_pool_invariants(ctx)
+    }
+
     pub fn process_initialize_liquidity_provider(ctx: Context<InitializeLiquidityProvider>) -> Result<()> {
         instructions::init_liquidity_provider::initialize_liquidity_provider(ctx)
     }
@@ -45,6 +101,30 @@ pub mod magic_block_liquiditypool {
         instructions::delegate_liquidity_provider(ctx, commit_frequency, validator_key)
     }
 
+    pub fn process_commit_and_undelegate_liquidity_provider(ctx: Context<CommitAndUndelegateLiquidityProvider>) -> Result<()> {
+        instructions::commit_pool::commit_and_undelegate_liquidity_provider(ctx)
+    }
+
+    pub fn process_initialize_lp_position(ctx: Context<InitializeLpPosition>) -> Result<()> {
+        instructions::init_lp_position::initialize_lp_position(ctx)
+    }
+
+    pub fn process_delegate_lp_position(ctx: Context<DelegateLpPosition>, commit_frequency: u32, validator_key: Pubkey) -> Result<()> {
+        instructions::delegate_lp_position::delegate_lp_position(ctx, commit_frequency, validator_key)
+    }
+
+    pub fn process_commit_and_undelegate_lp_position(ctx: Context<CommitAndUndelegateLpPosition>) -> Result<()> {
+        instructions::delegate_lp_position::commit_and_undelegate_lp_position(ctx)
+    }
+
+    pub fn process_close_liquidity_provider(ctx: Context<CloseLiquidityProvider>) -> Result<()> {
+        instructions::close_accounts::close_liquidity_provider(ctx)
+    }
+
+    pub fn process_migrate_lp_positions<'info>(ctx: Context<'_, '_, 'info, 'info, MigrateLpPositions<'info>>) -> Result<()> {
+        instructions::migrate_lp_positions::migrate_lp_positions(ctx)
+    }
+
     pub fn process_deposit_add_liquidity_on_chain(
         ctx: Context<DepositLiquidityOnchain>, 
         params: DepositLiquidityParams
@@ -60,32 +140,58 @@ pub mod magic_block_liquiditypool {
         instructions::add_liquidity_er::add_liquidity_er(ctx)
     }
 
-    pub fn process_mint_lp_tokens(ctx: Context<MintLpTokens>, mint_amount: u64) -> Result<()> {
-        instructions::mint_lp_tokens::mint_lp_tokens(ctx, mint_amount)
+    pub fn process_mint_lp_tokens(ctx: Context<MintLpTokens>) -> Result<()> {
+        instructions::mint_lp_tokens::mint_lp_tokens(ctx)
     }
-    
-    pub fn process_close_deposit_receipt(ctx: Context<CloseDepositReceiptInfo>) -> Result<()> {
-        instructions::add_liquidity_on_chain::close_deposit_receipt(ctx)
+
+    pub fn process_retry_mint_lp_tokens(ctx: Context<RetryMintLpTokens>) -> Result<()> {
+        instructions::mint_lp_tokens::retry_mint_lp_tokens(ctx)
+    }
+
+    pub fn process_commit_and_undelegate_deposit_receipt(ctx: Context<CommitAndUndelegateDepositReceipt>) -> Result<()> {
+        instructions::add_liquidity_on_chain::commit_and_undelegate_deposit_receipt(ctx)
+    }
+
+    pub fn process_cancel_deposit_receipt(ctx: Context<CancelDepositReceipt>) -> Result<()> {
+        instructions::cancel_receipt::cancel_deposit_receipt(ctx)
     }
 
     pub fn process_commit_and_mint_lp_tokens(ctx: Context<CommitAndMintLpTokens>) -> Result<()> {
 
         let deposit_recept = &ctx.accounts.deposit_recept;
 
+        require!(deposit_recept.status == ReceiptStatus::AppliedInER, error::ErrorCode::InvalidReceiptStatus);
+
+        // Same account set as the burn path: the base-layer pool has to reflect the deposit
+        // before the receipt it settles against comes back
+        ephemeral_rollups_sdk::ephem::commit_accounts(
+            &ctx.accounts.provider,
+            vec![
+                &ctx.accounts.pool.to_account_info(),
+                &ctx.accounts.liquidity_provider.to_account_info(),
+                &ctx.accounts.lp_position.to_account_info(),
+            ],
+            &ctx.accounts.magic_context,
+            &ctx.accounts.magic_program,
+        )?;
+
         let mint_instruction_data = anchor_lang::InstructionData::data(
-            &crate::instruction::ProcessMintLpTokens {
-                mint_amount: deposit_recept.lp_tokens_minted,
-            }
+            &crate::instruction::ProcessMintLpTokens {}
         );
 
         let action_args = ActionArgs {
-            escrow_index: 0,
+            escrow_index: action_guard::ACTION_ESCROW_INDEX,
             data: mint_instruction_data,
         };
 
+        // The provider receives the receipt rent when the handler closes it
         let accounts = vec![
             ShortAccountMeta {
                 pubkey: ctx.accounts.provider.key(),
+                is_writable: true,
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.pool.key(),
                 is_writable: false,
             },
             ShortAccountMeta {
@@ -101,49 +207,45 @@ pub mod magic_block_liquiditypool {
                 is_writable: true,
             },
             ShortAccountMeta {
-                pubkey: ctx.accounts.token_program.key(),
-                is_writable: false
+                pubkey: ctx.accounts.lp_token_account.key(),
+                is_writable: true,
             },
-        ];
-
-        let mint_handler = CallHandler {
-            args: action_args,
-            compute_units: 200_000,
-            escrow_authority: ctx.accounts.provider.to_account_info(),
-            destination_program: crate::ID,
-            accounts
-        };
-
-        let close_ix_data = anchor_lang::InstructionData::data(
-            &crate::instruction::ProcessCloseDepositReceipt {}
-        );
-
-        let action_args_close = ActionArgs {
-            escrow_index: 0,
-            data: close_ix_data
-        };
-
-        let close_handler_accounts = vec![
             ShortAccountMeta {
-                pubkey: ctx.accounts.provider.key(),
-                is_writable: true
+                pubkey: ctx.accounts.token_vault_a.key(),
+                is_writable: true,
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.token_vault_b.key(),
+                is_writable: true,
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.provider_token_a_ata.key(),
+                is_writable: true,
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.provider_token_b_ata.key(),
+                is_writable: true,
             },
             ShortAccountMeta {
                 pubkey: ctx.accounts.deposit_recept.key(),
-                is_writable: true
+                is_writable: true,
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.receipt_counter.key(),
+                is_writable: true,
             },
             ShortAccountMeta {
-                pubkey: ctx.accounts.system_program.key(),
+                pubkey: ctx.accounts.token_program.key(),
                 is_writable: false
-            }
+            },
         ];
 
-        let close_handler = CallHandler {
-            args: action_args_close,
+        let mint_handler = CallHandler {
+            args: action_args,
             compute_units: 200_000,
             escrow_authority: ctx.accounts.provider.to_account_info(),
             destination_program: crate::ID,
-            accounts: close_handler_accounts
+            accounts
         };
 
         MagicInstructionBuilder {
@@ -178,28 +280,98 @@ pub mod magic_block_liquiditypool {
         instructions::remove_liquidity_er(ctx, params)
     }
 
-    pub fn process_burn_lp_tokens(ctx: Context<BurnLpTokens>, burn_amount: u64) -> Result<()> {
-        instructions::burn_lp_tokens::burn_lp_tokens(ctx, burn_amount)
+    pub fn process_commit_and_undelegate_withdraw_receipt(ctx: Context<CommitAndUndelegateWithdrawReceipt>) -> Result<()> {
+        instructions::remove_liquidity_on_chain::commit_and_undelegate_withdraw_receipt(ctx)
+    }
+
+    pub fn process_cancel_withdraw_receipt(ctx: Context<CancelWithdrawReceipt>) -> Result<()> {
+        instructions::cancel_receipt::cancel_withdraw_receipt(ctx)
+    }
+
+    pub fn process_close_withdraw_receipt(ctx: Context<CloseWithdrawReceipt>) -> Result<()> {
+        instructions::close_accounts::close_withdraw_receipt(ctx)
+    }
+
+    pub fn process_swap(ctx: Context<Swap>, params: SwapParams) -> Result<()> {
+        instructions::swap::swap(ctx, params)
+    }
+
+    pub fn process_swap_on_chain(ctx: Context<SwapOnChain>, params: SwapOnChainParams) -> Result<()> {
+        instructions::swap_on_chain::swap_on_chain(ctx, params)
+    }
+
+    pub fn process_delegate_swap_receipt(ctx: Context<DelegateSwapReceipt>, commit_frequency: u32, validator_key: Pubkey) -> Result<()> {
+        instructions::swap_on_chain::delegate_swap_receipt(ctx, commit_frequency, validator_key)
+    }
+
+    pub fn process_commit_and_undelegate_swap_receipt(ctx: Context<CommitAndUndelegateSwapReceipt>) -> Result<()> {
+        instructions::swap_on_chain::commit_and_undelegate_swap_receipt(ctx)
+    }
+
+    pub fn process_cancel_swap_receipt(ctx: Context<CancelSwapReceipt>) -> Result<()> {
+        instructions::cancel_receipt::cancel_swap_receipt(ctx)
+    }
+
+    pub fn process_swap_er(ctx: Context<SwapER>) -> Result<()> {
+        instructions::swap_er::swap_er(ctx)
+    }
+
+    pub fn process_commit_and_settle_swap(ctx: Context<CommitAndSettleSwap>) -> Result<()> {
+        instructions::swap_er::commit_and_settle_swap(ctx)
+    }
+
+    pub fn process_settle_swap(ctx: Context<SettleSwap>) -> Result<()> {
+        instructions::swap_on_chain::settle_swap(ctx)
+    }
+
+    pub fn process_retry_settle_swap(ctx: Context<RetrySettleSwap>) -> Result<()> {
+        instructions::swap_on_chain::retry_settle_swap(ctx)
+    }
+
+    pub fn process_burn_lp_tokens(ctx: Context<BurnLpTokens>) -> Result<()> {
+        instructions::burn_lp_tokens::burn_lp_tokens(ctx)
+    }
+
+    pub fn process_retry_burn_lp_tokens(ctx: Context<RetryBurnLpTokens>) -> Result<()> {
+        instructions::burn_lp_tokens::retry_burn_lp_tokens(ctx)
     }
 
     pub fn process_commit_and_burn_lp_tokens(ctx: Context<CommitAndBurnMintLpTokens>) -> Result<()> {
 
-        let withdraw_recept = &ctx.accounts.withdraw_recept;
+        require!(
+            ctx.accounts.withdraw_recept.status == ReceiptStatus::AppliedInER,
+            error::ErrorCode::InvalidReceiptStatus
+        );
+
+        // Pool, provider and position stay delegated; only the receipt comes back for the handler to settle
+        ephemeral_rollups_sdk::ephem::commit_accounts(
+            &ctx.accounts.provider,
+            vec![
+                &ctx.accounts.pool.to_account_info(),
+                &ctx.accounts.liquidity_provider.to_account_info(),
+                &ctx.accounts.lp_position.to_account_info(),
+            ],
+            &ctx.accounts.magic_context,
+            &ctx.accounts.magic_program,
+        )?;
 
         let instruction_data = anchor_lang::InstructionData::data(
-            &crate::instruction::ProcessBurnLpTokens {
-                burn_amount: withdraw_recept.lp_tokens_to_burn
-            }
+            &crate::instruction::ProcessBurnLpTokens {}
         );
 
         let action_args = ActionArgs {
-            escrow_index: 0,
+            escrow_index: action_guard::ACTION_ESCROW_INDEX,
             data: instruction_data
         };
 
+        // The provider receives the escrow and receipt rent when the handler closes them
         let accounts = vec![
             ShortAccountMeta {
                 pubkey: ctx.accounts.provider.key(),
+                is_writable: true
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.pool.key(),
                 is_writable: false
             },
             ShortAccountMeta {
@@ -211,16 +383,40 @@ pub mod magic_block_liquiditypool {
                 is_writable: true,
             },
             ShortAccountMeta {
-                pubkey: ctx.accounts.provider_lp_ata.key(),
+                pubkey: ctx.accounts.withdraw_lp_escrow.key(),
                 is_writable: true
             },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.token_vault_a.key(),
+                is_writable: true,
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.token_vault_b.key(),
+                is_writable: true,
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.provider_token_a_ata.key(),
+                is_writable: true,
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.provider_token_b_ata.key(),
+                is_writable: true,
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.withdraw_recept.key(),
+                is_writable: true
+            },
+            ShortAccountMeta {
+                pubkey: ctx.accounts.receipt_counter.key(),
+                is_writable: true,
+            },
             ShortAccountMeta {
                 pubkey: ctx.accounts.token_program.key(),
                 is_writable: false
             }
         ];
 
-        let call_handler = CallHandler {
+        let burn_handler = CallHandler {
             args: action_args,
             compute_units: 200_000,
             escrow_authority: ctx.accounts.provider.to_account_info(),
@@ -228,21 +424,19 @@ pub mod magic_block_liquiditypool {
             accounts
         };
 
-        let magic_builder = MagicInstructionBuilder {
+        MagicInstructionBuilder {
             payer: ctx.accounts.provider.to_account_info(),
             magic_context: ctx.accounts.magic_context.to_account_info(),
             magic_program: ctx.accounts.magic_program.to_account_info(),
-            magic_action: MagicAction::Commit(CommitType::WithHandler {
-                commited_accounts: vec![
-                    ctx.accounts.pool.to_account_info(),
-                    ctx.accounts.liquidity_provider.to_account_info(),
-                    ctx.accounts.withdraw_recept.to_account_info(),
-                ],
-                call_handlers: vec![call_handler]
-            })
-        };
-
-        magic_builder.build_and_invoke()?;
+            magic_action: MagicAction::CommitAndUndelegate(
+                CommitAndUndelegate {
+                    commit_type: CommitType::Standalone(
+                        vec![ctx.accounts.withdraw_recept.to_account_info()]
+                    ),
+                    undelegate_type: UndelegateType::WithHandler(vec![burn_handler])
+                }
+            )
+        }.build_and_invoke()?;
 
         Ok(())
     }
@@ -271,8 +465,20 @@ pub struct CommitAndMintLpTokens<'info> {
 
     #[account(
         mut,
-        seeds = [b"deposit_recept", provider.key().as_ref()],
-        bump,
+        seeds = [b"lp_position", pool.key().as_ref(), provider.key().as_ref()],
+        bump = lp_position.bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"deposit_recept",
+            pool.key().as_ref(),
+            provider.key().as_ref(),
+            deposit_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = deposit_recept.bump,
     )]
     pub deposit_recept: Account<'info, DepositRecept>,
 
@@ -282,10 +488,49 @@ pub struct CommitAndMintLpTokens<'info> {
     )]
     pub transfer_authority: UncheckedAccount<'info>,
 
+    /// CHECK: Pool LP mint, resolved from the pool key
+    #[account(
+        seeds = [b"lp_token_mint", pool.key().as_ref()],
+        bump
+    )]
     pub lp_mint: UncheckedAccount<'info>,
 
     pub provider_lp_ata: UncheckedAccount<'info>,
 
+    /// CHECK: Pool-owned LP token account that holds the locked minimum liquidity
+    #[account(
+        seeds = [b"lp_token_account", pool.key().as_ref()],
+        bump
+    )]
+    pub lp_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: Pool vault A, source of deposit refunds
+    #[account(
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump
+    )]
+    pub token_vault_a: UncheckedAccount<'info>,
+
+    /// CHECK: Pool vault B, source of deposit refunds
+    #[account(
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump
+    )]
+    pub token_vault_b: UncheckedAccount<'info>,
+
+    /// CHECK: Provider's token A account, validated by the mint handler
+    pub provider_token_a_ata: UncheckedAccount<'info>,
+
+    /// CHECK: Provider's token B account, validated by the mint handler
+    pub provider_token_b_ata: UncheckedAccount<'info>,
+
+    /// CHECK: The pool's receipt counter, decremented by the mint handler on the base layer
+    #[account(
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump
+    )]
+    pub receipt_counter: UncheckedAccount<'info>,
+
     pub token_program: Program<'info, Token>,
 
     pub system_program: Program<'info, System>,
@@ -320,8 +565,20 @@ pub struct CommitAndBurnMintLpTokens<'info> {
 
     #[account(
         mut,
-        seeds = [b"withdraw_recept", provider.key().as_ref()],
-        bump,
+        seeds = [b"lp_position", pool.key().as_ref(), provider.key().as_ref()],
+        bump = lp_position.bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"withdraw_recept",
+            pool.key().as_ref(),
+            provider.key().as_ref(),
+            withdraw_recept.receipt_id.to_le_bytes().as_ref()
+        ],
+        bump = withdraw_recept.bump,
     )]
     pub withdraw_recept: Account<'info, WithdrawRecept>,
 
@@ -331,9 +588,46 @@ pub struct CommitAndBurnMintLpTokens<'info> {
     )]
     pub transfer_authority: UncheckedAccount<'info>,
 
+    /// CHECK: Pool LP mint, resolved from the pool key
+    #[account(
+        seeds = [b"lp_token_mint", pool.key().as_ref()],
+        bump
+    )]
     pub lp_mint: UncheckedAccount<'info>,
 
-    pub provider_lp_ata: UncheckedAccount<'info>,
+    /// CHECK: The receipt's LP escrow, burned from by the burn handler
+    #[account(
+        seeds = [b"withdraw_lp_escrow", withdraw_recept.key().as_ref()],
+        bump
+    )]
+    pub withdraw_lp_escrow: UncheckedAccount<'info>,
+
+    /// CHECK: Pool vault A, source of the withdrawal payout
+    #[account(
+        seeds = [b"token_account_a", pool.key().as_ref()],
+        bump
+    )]
+    pub token_vault_a: UncheckedAccount<'info>,
+
+    /// CHECK: Pool vault B, source of the withdrawal payout
+    #[account(
+        seeds = [b"token_account_b", pool.key().as_ref()],
+        bump
+    )]
+    pub token_vault_b: UncheckedAccount<'info>,
+
+    /// CHECK: Provider's token A account, validated by the burn handler
+    pub provider_token_a_ata: UncheckedAccount<'info>,
+
+    /// CHECK: Provider's token B account, validated by the burn handler
+    pub provider_token_b_ata: UncheckedAccount<'info>,
+
+    /// CHECK: The pool's receipt counter, decremented by the burn handler on the base layer
+    #[account(
+        seeds = [b"receipt_counter", pool.key().as_ref()],
+        bump
+    )]
+    pub receipt_counter: UncheckedAccount<'info>,
 
     pub token_program: Program<'info, Token>,
 
@@ -0,0 +1,641 @@
+// Constant-product curve shared by the on-chain and ER instructions. Every quote is priced against
+// the pool's virtual reserves and `total_lp_supply`, never against vault balances or the LP mint supply,
+// so both sides of a receipt see the same numbers. Every rounding step goes against the user.
+
+use anchor_lang::prelude::*;
+
+use crate::constants::{BPS_DENOMINATOR, MINIMUM_LIQUIDITY};
+use crate::error::ErrorCode;
+use crate::math::isqrt;
+use crate::state::pool::FeeConfig;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, InitSpace)]
+pub enum SwapMode {
+    ExactIn { amount_in: u64, min_amount_out: u64 },
+    ExactOut { amount_out: u64, max_amount_in: u64 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepositQuote {
+    // LP credited to the depositor
+    pub lp_tokens: u64,
+    // LP locked in the pool's `lp_token_account`; only non-zero on the first deposit
+    pub locked_liquidity: u64,
+    // Amounts the LP is actually worth at the pool ratio, rounded up and never more than offered
+    pub amount_a: u64,
+    pub amount_b: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WithdrawQuote {
+    pub amount_a: u64,
+    pub amount_b: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SingleTokenWithdrawQuote {
+    // Pro-rata share taken out of the reserves
+    pub withdraw: WithdrawQuote,
+    // The unwanted leg swapped back into the pool; `None` when that leg rounds to zero
+    pub swap: Option<SwapQuote>,
+    // Paid out: the chosen token's share plus the swap output, and nothing of the other token
+    pub amount_a: u64,
+    pub amount_b: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZapQuote {
+    // The internal swap, priced like a regular exact-in swap
+    pub swap: SwapQuote,
+    // The deposit of the unswapped remainder and the swap output, against the post-swap reserves
+    pub deposit: DepositQuote,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SwapQuote {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    // Part of `fee` owed to the protocol; the rest stays with the LPs
+    pub protocol_fee: u64,
+}
+
+/// LP minted for depositing `amount_a` and `amount_b`. The first deposit mints `sqrt(a * b)` rounded down,
+/// minus `MINIMUM_LIQUIDITY` which is locked; later deposits get the smaller of the two proportional shares, rounded down.
+pub fn quote_deposit(
+    reserve_a: u64,
+    reserve_b: u64,
+    total_lp_supply: u64,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<DepositQuote> {
+    require!(amount_a > 0 && amount_b > 0, ErrorCode::InvalidAmount);
+
+    if total_lp_supply == 0 {
+        let product = (amount_a as u128)
+            .checked_mul(amount_b as u128)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let initial_lp = u64::try_from(isqrt(product)).map_err(|_| ErrorCode::MathOverflow)?;
+        require!(initial_lp > MINIMUM_LIQUIDITY, ErrorCode::InsufficientLiquidity);
+
+        return Ok(DepositQuote {
+            lp_tokens: initial_lp - MINIMUM_LIQUIDITY,
+            locked_liquidity: MINIMUM_LIQUIDITY,
+            amount_a,
+            amount_b,
+        });
+    }
+
+    require!(reserve_a > 0 && reserve_b > 0, ErrorCode::InsufficientReserves);
+
+    let share_a = mul_div_floor(amount_a, total_lp_supply, reserve_a)?;
+    let share_b = mul_div_floor(amount_b, total_lp_supply, reserve_b)?;
+    let lp_tokens = std::cmp::min(share_a, share_b);
+
+    require!(lp_tokens > 0, ErrorCode::InvalidAmount);
+
+    Ok(DepositQuote {
+        lp_tokens,
+        locked_liquidity: 0,
+        amount_a: mul_div_ceil(lp_tokens, reserve_a, total_lp_supply)?,
+        amount_b: mul_div_ceil(lp_tokens, reserve_b, total_lp_supply)?,
+    })
+}
+
+/// Single-sided deposit of `amount_in`: swaps the largest part of it that still leaves the remainder
+/// worth at least the swap output at the post-swap price, then deposits both legs. The swap pays
+/// the trade fee like any other; whatever the deposit doesn't use is left for the caller to refund.
+pub fn quote_zap_deposit(
+    reserve_a: u64,
+    reserve_b: u64,
+    total_lp_supply: u64,
+    fees: &FeeConfig,
+    a_to_b: bool,
+    amount_in: u64,
+) -> Result<ZapQuote> {
+    require!(amount_in > 0, ErrorCode::InvalidAmount);
+    // The pool has no price to swap at before its first deposit
+    require!(total_lp_supply > 0, ErrorCode::InsufficientReserves);
+
+    let (reserve_in, reserve_out) = if a_to_b { (reserve_a, reserve_b) } else { (reserve_b, reserve_a) };
+    require!(reserve_in > 0 && reserve_out > 0, ErrorCode::InsufficientReserves);
+
+    // Keeps the remainder on the input side of the pool ratio, so the swap output is the limiting leg
+    let leaves_input_heavy = |swap: &SwapQuote| -> Result<bool> {
+        let remaining_in = (amount_in - swap.amount_in) as u128;
+        let reserve_in_after = reserve_in as u128 + (swap.amount_in - swap.protocol_fee) as u128;
+        let reserve_out_after = (reserve_out - swap.amount_out) as u128;
+
+        let input_side = remaining_in
+            .checked_mul(reserve_out_after)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let output_side = (swap.amount_out as u128)
+            .checked_mul(reserve_in_after)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(input_side >= output_side)
+    };
+
+    // Binary search for the largest swap amount that still satisfies it
+    let (mut low, mut high) = (0u64, amount_in);
+    while low < high {
+        let mid = low + (high - low).div_ceil(2);
+        let swap = quote_exact_in(reserve_in as u128, reserve_out as u128, fees, mid)?;
+
+        if leaves_input_heavy(&swap)? {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    let swap = quote_exact_in(reserve_in as u128, reserve_out as u128, fees, low)?;
+    require!(swap.amount_out > 0, ErrorCode::InvalidAmount);
+
+    let reserve_in_after = reserve_in
+        .checked_add(swap.amount_in - swap.protocol_fee)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let reserve_out_after = reserve_out - swap.amount_out;
+    let remaining_in = amount_in - swap.amount_in;
+
+    let deposit = if a_to_b {
+        quote_deposit(reserve_in_after, reserve_out_after, total_lp_supply, remaining_in, swap.amount_out)?
+    } else {
+        quote_deposit(reserve_out_after, reserve_in_after, total_lp_supply, swap.amount_out, remaining_in)?
+    };
+
+    Ok(ZapQuote { swap, deposit })
+}
+
+/// Tokens paid out for burning `lp_tokens`: the proportional share of each reserve, rounded down.
+pub fn quote_withdraw(
+    reserve_a: u64,
+    reserve_b: u64,
+    total_lp_supply: u64,
+    lp_tokens: u64,
+) -> Result<WithdrawQuote> {
+    require!(lp_tokens > 0, ErrorCode::InvalidAmount);
+    require!(total_lp_supply > 0, ErrorCode::InvalidPoolState);
+    require!(lp_tokens <= total_lp_supply, ErrorCode::InsufficientLpTokens);
+
+    Ok(WithdrawQuote {
+        amount_a: mul_div_floor(lp_tokens, reserve_a, total_lp_supply)?,
+        amount_b: mul_div_floor(lp_tokens, reserve_b, total_lp_supply)?,
+    })
+}
+
+/// Tokens paid out for burning `lp_tokens` into a single token: the pro-rata share, with the unwanted leg
+/// swapped back through the curve against the post-withdrawal reserves. The swap pays the trade fee like any other.
+pub fn quote_withdraw_single(
+    reserve_a: u64,
+    reserve_b: u64,
+    total_lp_supply: u64,
+    fees: &FeeConfig,
+    lp_tokens: u64,
+    to_a: bool,
+) -> Result<SingleTokenWithdrawQuote> {
+    let withdraw = quote_withdraw(reserve_a, reserve_b, total_lp_supply, lp_tokens)?;
+
+    // `withdraw` never exceeds the reserves since `lp_tokens <= total_lp_supply`
+    let (reserve_in, reserve_out, leg_in, leg_out) = if to_a {
+        (reserve_b - withdraw.amount_b, reserve_a - withdraw.amount_a, withdraw.amount_b, withdraw.amount_a)
+    } else {
+        (reserve_a - withdraw.amount_a, reserve_b - withdraw.amount_b, withdraw.amount_a, withdraw.amount_b)
+    };
+
+    let swap = if leg_in > 0 {
+        require!(reserve_in > 0 && reserve_out > 0, ErrorCode::InsufficientReserves);
+        Some(quote_exact_in(reserve_in as u128, reserve_out as u128, fees, leg_in)?)
+    } else {
+        None
+    };
+
+    let amount_out = leg_out
+        .checked_add(swap.map_or(0, |swap| swap.amount_out))
+        .ok_or(ErrorCode::MathOverflow)?;
+    require!(amount_out > 0, ErrorCode::InvalidAmount);
+
+    let (amount_a, amount_b) = if to_a { (amount_out, 0) } else { (0, amount_out) };
+
+    Ok(SingleTokenWithdrawQuote { withdraw, swap, amount_a, amount_b })
+}
+
+/// Prices a swap against the x*y=k curve, charging `trade_fee_bps` on the input side.
+/// Every rounding step favours the pool; the protocol share of the fee rounds down.
+pub fn quote_swap(reserve_in: u64, reserve_out: u64, fees: &FeeConfig, mode: SwapMode) -> Result<SwapQuote> {
+    require!(reserve_in > 0 && reserve_out > 0, ErrorCode::InsufficientReserves);
+
+    let reserve_in = reserve_in as u128;
+    let reserve_out = reserve_out as u128;
+    let fee_bps = fees.trade_fee_bps as u128;
+
+    match mode {
+        SwapMode::ExactIn { amount_in, min_amount_out } => {
+            require!(amount_in > 0, ErrorCode::InvalidAmount);
+
+            let quote = quote_exact_in(reserve_in, reserve_out, fees, amount_in)?;
+
+            require!(quote.amount_out > 0, ErrorCode::InvalidAmount);
+            require!((quote.amount_out as u128) < reserve_out, ErrorCode::InsufficientReserves);
+            require!(quote.amount_out >= min_amount_out, ErrorCode::SlippageExceeded);
+
+            Ok(quote)
+        }
+        SwapMode::ExactOut { amount_out, max_amount_in } => {
+            require!(amount_out > 0, ErrorCode::InvalidAmount);
+            require!((amount_out as u128) < reserve_out, ErrorCode::InsufficientReserves);
+
+            let amount_in_after_fee = reserve_in
+                .checked_mul(amount_out as u128)
+                .ok_or(ErrorCode::MathOverflow)?
+                .div_ceil(reserve_out - amount_out as u128);
+
+            let amount_in = amount_in_after_fee
+                .checked_mul(BPS_DENOMINATOR)
+                .ok_or(ErrorCode::MathOverflow)?
+                .div_ceil(
+                    BPS_DENOMINATOR
+                        .checked_sub(fee_bps)
+                        .filter(|denominator| *denominator > 0)
+                        .ok_or(ErrorCode::InvalidPoolState)?,
+                );
+
+            require!(amount_in <= max_amount_in as u128, ErrorCode::SlippageExceeded);
+
+            let fee = amount_in - amount_in_after_fee;
+
+            Ok(SwapQuote {
+                amount_in: amount_in as u64,
+                amount_out,
+                fee: fee as u64,
+                protocol_fee: protocol_fee(amount_in, fee, fees.protocol_fee_bps)?,
+            })
+        }
+    }
+}
+
+// Exact-in pricing without the limits, so callers can probe amounts that would round to nothing
+fn quote_exact_in(reserve_in: u128, reserve_out: u128, fees: &FeeConfig, amount_in: u64) -> Result<SwapQuote> {
+    let fee = (amount_in as u128)
+        .checked_mul(fees.trade_fee_bps as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .div_ceil(BPS_DENOMINATOR);
+
+    let amount_in_after_fee = (amount_in as u128)
+        .checked_sub(fee)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // Below `reserve_out` for any finite input, so it fits in a u64
+    let amount_out = amount_in_after_fee
+        .checked_mul(reserve_out)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(
+            reserve_in
+                .checked_add(amount_in_after_fee)
+                .ok_or(ErrorCode::MathOverflow)?,
+        )
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(SwapQuote {
+        amount_in,
+        amount_out: amount_out as u64,
+        fee: fee as u64,
+        protocol_fee: protocol_fee(amount_in as u128, fee, fees.protocol_fee_bps)?,
+    })
+}
+
+// Protocol share is charged on volume like the trade fee, and never exceeds it
+fn protocol_fee(amount_in: u128, fee: u128, protocol_fee_bps: u16) -> Result<u64> {
+    let protocol_fee = amount_in
+        .checked_mul(protocol_fee_bps as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        / BPS_DENOMINATOR;
+
+    Ok(std::cmp::min(protocol_fee, fee) as u64)
+}
+
+fn mul_div_floor(a: u64, b: u64, denominator: u64) -> Result<u64> {
+    require!(denominator > 0, ErrorCode::MathOverflow);
+
+    let result = (a as u128)
+        .checked_mul(b as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        / denominator as u128;
+
+    u64::try_from(result).map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+fn mul_div_ceil(a: u64, b: u64, denominator: u64) -> Result<u64> {
+    require!(denominator > 0, ErrorCode::MathOverflow);
+
+    let result = (a as u128)
+        .checked_mul(b as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .div_ceil(denominator as u128);
+
+    u64::try_from(result).map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fees(trade_fee_bps: u16, protocol_fee_bps: u16) -> FeeConfig {
+        FeeConfig { trade_fee_bps, protocol_fee_bps, fee_recipient: Pubkey::default() }
+    }
+
+    fn is_err(result: Result<impl std::fmt::Debug>, code: ErrorCode) -> bool {
+        result.unwrap_err() == code.into()
+    }
+
+    #[test]
+    fn first_deposit_locks_minimum_liquidity() {
+        let quote = quote_deposit(0, 0, 0, 4_000_000, 1_000_000).unwrap();
+        assert_eq!(quote.lp_tokens, 2_000_000 - MINIMUM_LIQUIDITY);
+        assert_eq!(quote.locked_liquidity, MINIMUM_LIQUIDITY);
+
+        // sqrt(1001 * 1001) leaves a single LP token for the depositor
+        assert_eq!(quote_deposit(0, 0, 0, 1_001, 1_001).unwrap().lp_tokens, 1);
+        assert!(is_err(quote_deposit(0, 0, 0, 1_000, 1_000), ErrorCode::InsufficientLiquidity));
+    }
+
+    #[test]
+    fn first_deposit_rounds_down() {
+        // sqrt(2_000_000 * 1_000_001) = 1_414_214.27...
+        let quote = quote_deposit(0, 0, 0, 2_000_000, 1_000_001).unwrap();
+        assert_eq!(quote.lp_tokens + quote.locked_liquidity, 1_414_214);
+    }
+
+    #[test]
+    fn deposit_takes_smaller_share_rounded_down() {
+        // A alone would be worth 100 LP, B alone 150 LP
+        let quote = quote_deposit(1_000, 2_000, 1_000, 100, 300).unwrap();
+        assert_eq!(quote, DepositQuote { lp_tokens: 100, locked_liquidity: 0, amount_a: 100, amount_b: 200 });
+
+        // 7 * 1_000 / 3_000 = 2.33
+        assert_eq!(quote_deposit(3_000, 3_000, 1_000, 7, 7).unwrap().lp_tokens, 2);
+        assert!(is_err(quote_deposit(3_000, 3_000, 1_000, 2, 2), ErrorCode::InvalidAmount));
+    }
+
+    #[test]
+    fn deposit_takes_matched_amounts_rounded_up() {
+        // 10 LP is worth 33.3 A and 66.6 B: the depositor pays 34 and 67, and never more than offered
+        let quote = quote_deposit(3_333, 6_666, 1_000, 40, 67).unwrap();
+        assert_eq!(quote.lp_tokens, 10);
+        assert_eq!((quote.amount_a, quote.amount_b), (34, 67));
+
+        let quote = quote_deposit(7_919, 104_729, 31_337, 1_000_003, 9_999_991).unwrap();
+        assert!(quote.amount_a <= 1_000_003 && quote.amount_b <= 9_999_991);
+        assert!(quote.amount_a as u128 * 31_337 >= quote.lp_tokens as u128 * 7_919);
+        assert!(quote.amount_b as u128 * 31_337 >= quote.lp_tokens as u128 * 104_729);
+    }
+
+    #[test]
+    fn deposit_rejects_bad_inputs() {
+        assert!(is_err(quote_deposit(0, 0, 0, 0, 5_000), ErrorCode::InvalidAmount));
+        assert!(is_err(quote_deposit(0, 1_000, 1_000, 10, 10), ErrorCode::InsufficientReserves));
+    }
+
+    #[test]
+    fn deposit_handles_full_u64_range() {
+        let quote = quote_deposit(0, 0, 0, u64::MAX, u64::MAX).unwrap();
+        assert_eq!(quote.lp_tokens + quote.locked_liquidity, u64::MAX);
+
+        let quote = quote_deposit(u64::MAX, u64::MAX, u64::MAX, u64::MAX, u64::MAX).unwrap();
+        assert_eq!(quote.lp_tokens, u64::MAX);
+
+        // Proportional share larger than u64 must fail instead of truncating
+        assert!(is_err(quote_deposit(1, 1, u64::MAX, 2, 2), ErrorCode::MathOverflow));
+    }
+
+    #[test]
+    fn withdraw_is_proportional_and_rounds_down() {
+        let quote = quote_withdraw(1_000, 3_001, 2_000, 500).unwrap();
+        assert_eq!(quote, WithdrawQuote { amount_a: 250, amount_b: 750 });
+
+        assert_eq!(quote_withdraw(10, 10, 3, 1).unwrap(), WithdrawQuote { amount_a: 3, amount_b: 3 });
+        assert_eq!(quote_withdraw(u64::MAX, 7, u64::MAX, u64::MAX).unwrap().amount_a, u64::MAX);
+    }
+
+    #[test]
+    fn withdraw_rejects_bad_inputs() {
+        assert!(is_err(quote_withdraw(1_000, 1_000, 1_000, 0), ErrorCode::InvalidAmount));
+        assert!(is_err(quote_withdraw(1_000, 1_000, 0, 1), ErrorCode::InvalidPoolState));
+        assert!(is_err(quote_withdraw(1_000, 1_000, 1_000, 1_001), ErrorCode::InsufficientLpTokens));
+    }
+
+    #[test]
+    fn deposit_then_withdraw_never_profits() {
+        let (mut reserve_a, mut reserve_b, mut supply) = (1_000_003u64, 2_000_017u64, 1_414_227u64);
+
+        for (amount_a, amount_b) in [(1u64, 3u64), (17, 31), (999, 2_001), (123_457, 246_913)] {
+            let deposit = quote_deposit(reserve_a, reserve_b, supply, amount_a, amount_b);
+            let Ok(deposit) = deposit else { continue };
+
+            reserve_a += amount_a;
+            reserve_b += amount_b;
+            supply += deposit.lp_tokens;
+
+            let withdraw = quote_withdraw(reserve_a, reserve_b, supply, deposit.lp_tokens).unwrap();
+            assert!(withdraw.amount_a <= amount_a && withdraw.amount_b <= amount_b);
+
+            reserve_a -= withdraw.amount_a;
+            reserve_b -= withdraw.amount_b;
+            supply -= deposit.lp_tokens;
+        }
+    }
+
+    #[test]
+    fn single_token_withdraw_swaps_the_other_leg() {
+        let quote = quote_withdraw_single(1_000_000, 2_000_000, 1_000_000, &fees(30, 5), 100_000, true).unwrap();
+        assert_eq!(quote.withdraw, WithdrawQuote { amount_a: 100_000, amount_b: 200_000 });
+
+        // 200_000 B sold into the remaining 1_800_000 B / 900_000 A, after a 600 fee
+        let swap = quote.swap.unwrap();
+        assert_eq!(swap.amount_in, 200_000);
+        assert_eq!(swap.fee, 600);
+        assert_eq!(swap.amount_out, 199_400 * 900_000 / 1_999_400);
+        assert_eq!((quote.amount_a, quote.amount_b), (100_000 + swap.amount_out, 0));
+
+        // Worth less than the pro-rata share: the swap pays the fee and moves the price
+        let pro_rata_in_a = 100_000 + 200_000 / 2;
+        assert!(quote.amount_a < pro_rata_in_a);
+
+        let quote = quote_withdraw_single(1_000_000, 2_000_000, 1_000_000, &fees(30, 5), 100_000, false).unwrap();
+        assert_eq!(quote.amount_a, 0);
+        assert!(quote.amount_b > 200_000 && quote.amount_b < 400_000);
+    }
+
+    #[test]
+    fn single_token_withdraw_edge_cases() {
+        // The B leg rounds to zero, so there is nothing to swap
+        let quote = quote_withdraw_single(1_000_000, 10, 1_000_000, &fees(30, 0), 10, true).unwrap();
+        assert_eq!(quote.swap, None);
+        assert_eq!((quote.amount_a, quote.amount_b), (10, 0));
+
+        // Nothing left in the pool to swap against
+        assert!(is_err(
+            quote_withdraw_single(1_000, 1_000, 1_000, &fees(30, 0), 1_000, true),
+            ErrorCode::InsufficientReserves
+        ));
+        assert!(is_err(
+            quote_withdraw_single(1_000, 1_000, 1_000, &fees(30, 0), 0, true),
+            ErrorCode::InvalidAmount
+        ));
+    }
+
+    #[test]
+    fn zap_deposit_leaves_only_dust() {
+        let fee_config = fees(30, 5);
+        for (reserve_a, reserve_b, supply, amount_in) in [
+            (1_000_000u64, 1_000_000u64, 1_000_000u64, 100_000u64),
+            (5_000_000, 20_000_000, 10_000_000, 1_234_567),
+            (1_000_000_000_000, 3_000_000, 50_000_000, 777_777),
+        ] {
+            for a_to_b in [true, false] {
+                let zap = quote_zap_deposit(reserve_a, reserve_b, supply, &fee_config, a_to_b, amount_in).unwrap();
+                let (remaining_in, used_in, used_out) = if a_to_b {
+                    (amount_in - zap.swap.amount_in, zap.deposit.amount_a, zap.deposit.amount_b)
+                } else {
+                    (amount_in - zap.swap.amount_in, zap.deposit.amount_b, zap.deposit.amount_a)
+                };
+
+                assert!(used_in <= remaining_in && used_out <= zap.swap.amount_out);
+
+                // Unused tokens are refunded: at most a couple of LP units' worth, plus one unit of
+                // swap output's worth on the input side since the output only moves in whole units
+                let (reserve_in, reserve_out) = if a_to_b { (reserve_a, reserve_b) } else { (reserve_b, reserve_a) };
+                let dust_in = remaining_in - used_in;
+                let dust_out = zap.swap.amount_out - used_out;
+                assert!(
+                    dust_in <= 2 + 2 * reserve_in / supply + reserve_in / reserve_out,
+                    "dust in {} for {:?}",
+                    dust_in,
+                    zap
+                );
+                assert!(dust_out <= 2 + 2 * reserve_out / supply, "dust out {} for {:?}", dust_out, zap);
+            }
+        }
+    }
+
+    #[test]
+    fn zap_deposit_is_worth_less_than_balanced() {
+        // Both deposits hand the pool 200_000 A worth of value, but the zap pays the fee on what it swaps
+        let balanced = quote_deposit(1_000_000, 1_000_000, 1_000_000, 100_000, 100_000).unwrap();
+        let zap = quote_zap_deposit(1_000_000, 1_000_000, 1_000_000, &fees(30, 0), true, 200_000).unwrap();
+        assert!(zap.deposit.lp_tokens < balanced.lp_tokens);
+        assert!(zap.swap.fee > 0);
+    }
+
+    #[test]
+    fn zap_deposit_rejects_bad_inputs() {
+        assert!(is_err(quote_zap_deposit(0, 0, 0, &fees(30, 0), true, 1_000), ErrorCode::InsufficientReserves));
+        assert!(is_err(quote_zap_deposit(1_000, 1_000, 1_000, &fees(30, 0), true, 0), ErrorCode::InvalidAmount));
+        // Too small to swap anything out
+        assert!(is_err(quote_zap_deposit(1_000_000, 1_000, 1_000, &fees(30, 0), true, 3), ErrorCode::InvalidAmount));
+    }
+
+    #[test]
+    fn swap_exact_in_charges_fee_on_input() {
+        let quote = quote_swap(
+            1_000_000,
+            1_000_000,
+            &fees(30, 5),
+            SwapMode::ExactIn { amount_in: 10_000, min_amount_out: 0 },
+        )
+        .unwrap();
+
+        assert_eq!(quote.fee, 30);
+        assert_eq!(quote.protocol_fee, 5);
+        // 9_970 * 1_000_000 / 1_009_970 = 9_871.58
+        assert_eq!(quote.amount_out, 9_871);
+    }
+
+    #[test]
+    fn swap_exact_in_rounds_fee_up() {
+        let quote = quote_swap(
+            1_000_000,
+            1_000_000,
+            &fees(30, 30),
+            SwapMode::ExactIn { amount_in: 1, min_amount_out: 0 },
+        );
+        // A 1-token trade pays a full token of fee and gets nothing out
+        assert!(is_err(quote, ErrorCode::InvalidAmount));
+
+        let quote = quote_swap(
+            1_000_000,
+            1_000_000,
+            &fees(30, 30),
+            SwapMode::ExactIn { amount_in: 1_001, min_amount_out: 0 },
+        )
+        .unwrap();
+        assert_eq!(quote.fee, 4);
+        assert_eq!(quote.protocol_fee, 3);
+    }
+
+    #[test]
+    fn swap_exact_out_rounds_input_up() {
+        let quote = quote_swap(
+            1_000_000,
+            1_000_000,
+            &fees(30, 0),
+            SwapMode::ExactOut { amount_out: 9_871, max_amount_in: u64::MAX },
+        )
+        .unwrap();
+
+        assert_eq!(quote.amount_out, 9_871);
+        // Never dearer than the exact-in trade of 10_000 that yields this output
+        assert!(quote.amount_in <= 10_000);
+        // Enough to actually buy the output, and one unit less isn't
+        let back = quote_swap(
+            1_000_000,
+            1_000_000,
+            &fees(30, 0),
+            SwapMode::ExactIn { amount_in: quote.amount_in, min_amount_out: 0 },
+        )
+        .unwrap();
+        assert!(back.amount_out >= quote.amount_out);
+        let short = quote_swap(
+            1_000_000,
+            1_000_000,
+            &fees(30, 0),
+            SwapMode::ExactIn { amount_in: quote.amount_in - 1, min_amount_out: 0 },
+        )
+        .unwrap();
+        assert!(short.amount_out < quote.amount_out);
+    }
+
+    #[test]
+    fn swap_keeps_invariant_non_decreasing() {
+        let (reserve_in, reserve_out) = (7_777_777u64, 3_333_333u64);
+        for amount_in in [1_000u64, 54_321, 1_000_000, 50_000_000] {
+            let quote = quote_swap(
+                reserve_in,
+                reserve_out,
+                &fees(0, 0),
+                SwapMode::ExactIn { amount_in, min_amount_out: 0 },
+            )
+            .unwrap();
+
+            let k_before = reserve_in as u128 * reserve_out as u128;
+            let k_after = (reserve_in + quote.amount_in) as u128 * (reserve_out - quote.amount_out) as u128;
+            assert!(k_after >= k_before);
+        }
+    }
+
+    #[test]
+    fn swap_enforces_limits() {
+        let exact_in = SwapMode::ExactIn { amount_in: 10_000, min_amount_out: 9_872 };
+        assert!(is_err(quote_swap(1_000_000, 1_000_000, &fees(30, 0), exact_in), ErrorCode::SlippageExceeded));
+
+        let exact_out = SwapMode::ExactOut { amount_out: 9_871, max_amount_in: 9_000 };
+        assert!(is_err(quote_swap(1_000_000, 1_000_000, &fees(30, 0), exact_out), ErrorCode::SlippageExceeded));
+
+        let drain = SwapMode::ExactOut { amount_out: 1_000_000, max_amount_in: u64::MAX };
+        assert!(is_err(quote_swap(1_000_000, 1_000_000, &fees(30, 0), drain), ErrorCode::InsufficientReserves));
+
+        let empty = SwapMode::ExactIn { amount_in: 10, min_amount_out: 0 };
+        assert!(is_err(quote_swap(0, 1_000_000, &fees(30, 0), empty), ErrorCode::InsufficientReserves));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod sqrt;
+pub mod curve;
+
+pub use sqrt::*;
+pub use curve::*;
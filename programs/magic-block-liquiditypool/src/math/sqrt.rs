@@ -0,0 +1,77 @@
+/// Integer square root rounded down: the largest `r` with `r * r <= n`.
+/// Rounding down means LP minted from `sqrt(a * b)` never exceeds the deposit's true share.
+pub fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+
+    // Start above the root (2^ceil(bits / 2)) so Newton's iteration decreases monotonically to the floor
+    let bits = 128 - n.leading_zeros();
+    let mut x: u128 = 1 << bits.div_ceil(2);
+
+    loop {
+        let y = (x + n / x) >> 1;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_floor_root(n: u128, r: u128) -> bool {
+        let below = r.checked_mul(r).is_some_and(|sq| sq <= n);
+        let above = (r + 1).checked_mul(r + 1).is_none_or(|sq| sq > n);
+        below && above
+    }
+
+    #[test]
+    fn small_values_exhaustive() {
+        for n in 0..=1_000_000u128 {
+            assert!(is_floor_root(n, isqrt(n)), "isqrt({}) = {}", n, isqrt(n));
+        }
+    }
+
+    #[test]
+    fn perfect_squares_and_neighbours() {
+        for r in (0..=u64::MAX as u128).step_by(0x0000_1234_5678_9abd) {
+            let n = r * r;
+            assert_eq!(isqrt(n), r);
+            if n > 0 {
+                assert_eq!(isqrt(n - 1), r - 1);
+            }
+            if r > 0 {
+                assert_eq!(isqrt(n + 2 * r), r);
+            }
+        }
+    }
+
+    #[test]
+    fn powers_of_two() {
+        for shift in 0..128 {
+            let n = 1u128 << shift;
+            assert!(is_floor_root(n, isqrt(n)));
+            assert!(is_floor_root(n - 1, isqrt(n - 1)));
+        }
+    }
+
+    #[test]
+    fn beyond_f64_precision() {
+        // Just above 2^53 an f64 can no longer represent every integer
+        let r = (1u128 << 53) + 1;
+        assert_eq!(isqrt(r * r), r);
+        assert_eq!(isqrt(r * r - 1), r - 1);
+
+        let n = u64::MAX as u128 * (u64::MAX as u128 - 7);
+        assert!(is_floor_root(n, isqrt(n)));
+    }
+
+    #[test]
+    fn extremes() {
+        assert_eq!(isqrt(u128::MAX), u64::MAX as u128);
+        assert_eq!(isqrt((u64::MAX as u128) * (u64::MAX as u128)), u64::MAX as u128);
+    }
+}
@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::POOLS_PER_REGISTRY_PAGE;
+
+#[account]
+#[derive(Default, InitSpace, Debug)]
+pub struct Factory {
+    // Next pool id to hand out; also the number of pools created so far
+    pub pool_count: u64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl Factory {
+    pub fn registry_page_for(pool_id: u64) -> u64 {
+        pool_id / POOLS_PER_REGISTRY_PAGE as u64
+    }
+}
+
+// Pools are listed in id order, POOLS_PER_REGISTRY_PAGE per page, so clients can walk
+// pages 0..=(pool_count - 1) / POOLS_PER_REGISTRY_PAGE to enumerate every pool.
+#[account]
+#[derive(Default, InitSpace, Debug)]
+pub struct PoolRegistryPage {
+    pub page: u64,
+    #[max_len(POOLS_PER_REGISTRY_PAGE)]
+    pub pools: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+// One per (mint_a, mint_b, fee tier); mints are stored in canonical order (mint_a < mint_b)
+#[account]
+#[derive(Default, InitSpace, Debug)]
+pub struct PoolMapping {
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub fee_tier: u16,
+    pub pool: Pubkey,
+    pub pool_id: u64,
+    pub bump: u8,
+}
@@ -8,6 +8,7 @@ pub struct LiquidityPoolInfo {
     pub lp_tokens: u64,         
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for LiquidityPoolInfo {
     fn default() -> Self {
         LiquidityPoolInfo {
@@ -19,13 +20,16 @@ impl Default for LiquidityPoolInfo {
     }
 }
 
+// Aggregate header across all of a provider's `LpPosition` accounts
 #[account]
 #[derive(Debug, InitSpace, Copy)]
 pub struct LiquidityProvider {
     pub provider: Pubkey,                    
     pub total_liquidity_provided: u64,       
     pub total_lp_tokens: u64,                
-    pub liquidity_pools_info: [LiquidityPoolInfo; 10],
+    // Per-pool slots from before `LpPosition`; kept so existing accounts still deserialize,
+    // and only read by `migrate_lp_positions`, which moves them out and zeroes them
+    pub legacy_pools_info: [LiquidityPoolInfo; 10],
     pub latest_liquidity_provided_on: i64,   
     pub bump: u8,                            
 }
@@ -0,0 +1,15 @@
+use anchor_lang::prelude::*;
+
+// One per (pool, provider), seeded [b"lp_position", pool, provider], and delegated on its own,
+// so a provider can hold any number of pools; `LiquidityProvider` keeps the cross-pool totals
+#[account]
+#[derive(Debug, InitSpace)]
+pub struct LpPosition {
+    pub provider: Pubkey,
+    pub pool: Pubkey,
+    pub lp_mint: Pubkey,
+    pub liquidity_amount: u64,
+    pub lp_tokens: u64,
+    pub last_updated_at: i64,
+    pub bump: u8,
+}
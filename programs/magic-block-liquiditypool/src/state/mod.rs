@@ -1,2 +1,5 @@
 pub mod pool;
-pub mod liquidity_provider;
\ No newline at end of file
+pub mod liquidity_provider;
+pub mod lp_position;
+pub mod factory;
+pub mod receipt;
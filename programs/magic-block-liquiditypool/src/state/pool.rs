@@ -1,10 +1,19 @@
 use anchor_lang::prelude::*;
 
+use ephemeral_rollups_sdk::cpi::DELEGATION_PROGRAM_ID;
+
+use crate::constants::{MAX_TRADE_FEE_BPS, MAX_PROTOCOL_FEE_BPS};
+use crate::error::ErrorCode;
+
 #[account]
 #[derive(Default, InitSpace, Debug)]
 pub struct Pool {
     pub version: u8,
     pub authority: Pubkey,
+    // Human/multisig owner of the pool; `authority` is the transfer_authority PDA
+    pub admin: Pubkey,
+    // Set by `propose_admin`, becomes `admin` once it signs `accept_admin`
+    pub pending_admin: Pubkey,
     pub pool_id: u64,
     #[max_len(50)]
     pub name: String,
@@ -19,7 +28,14 @@ pub struct Pool {
     pub reserve_b: u64,
     pub total_lp_supply: u64,
 
+    // Protocol share of the trade fees, held in the vaults on top of the reserves until claimed
+    pub protocol_fees_owed_a: u64,
+    pub protocol_fees_owed_b: u64,
+
     pub fees: FeeConfig,
+    // The trade fee the pool was created with; seeds its `PoolMapping`, so it never changes
+    // even when `fees.trade_fee_bps` is updated
+    pub fee_tier: u16,
     pub status: PoolStatus,
     pub created_at: i64,
     pub updated_at: i64,
@@ -29,6 +45,47 @@ pub struct Pool {
     pub token_b_vault_bump: u8,
 }
 
+impl Pool {
+    // On the base layer a delegated pool is owned by the delegation program, so `Account<Pool>`
+    // can't load it; the owner, discriminator and seeds are checked here instead
+    pub fn load_checked(pool_info: &AccountInfo) -> Result<Pool> {
+        require!(
+            pool_info.owner == &crate::ID || pool_info.owner == &DELEGATION_PROGRAM_ID,
+            ErrorCode::InvalidPoolState
+        );
+
+        let pool = Pool::try_deserialize(&mut &pool_info.try_borrow_data()?[..])?;
+
+        let expected_pool = Pubkey::create_program_address(
+            &[b"pool", pool.name.as_bytes(), &[pool.bump]],
+            &crate::ID,
+        ).map_err(|_| error!(ErrorCode::InvalidPoolState))?;
+        require!(expected_pool == pool_info.key(), ErrorCode::InvalidPoolState);
+
+        Ok(pool)
+    }
+
+    pub fn require_tradable(&self) -> Result<()> {
+        require!(self.status.is_active, ErrorCode::PoolNotActive);
+        require!(!self.status.is_paused, ErrorCode::PoolPaused);
+        Ok(())
+    }
+
+    pub fn require_token_accounts(
+        &self,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        token_vault_a: &Pubkey,
+        token_vault_b: &Pubkey,
+    ) -> Result<()> {
+        require_keys_eq!(*mint_a, self.token_a, ErrorCode::InvalidPoolState);
+        require_keys_eq!(*mint_b, self.token_b, ErrorCode::InvalidPoolState);
+        require_keys_eq!(*token_vault_a, self.token_a_vault, ErrorCode::InvalidPoolState);
+        require_keys_eq!(*token_vault_b, self.token_b_vault, ErrorCode::InvalidPoolState);
+        Ok(())
+    }
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace, Debug)]
 pub struct FeeConfig {
     pub trade_fee_bps: u16,
@@ -36,6 +93,16 @@ pub struct FeeConfig {
     pub fee_recipient: Pubkey,
 }
 
+impl FeeConfig {
+    // The protocol fee is carved out of the trade fee, so it can never exceed it
+    pub fn validate_fees(trade_fee_bps: u16, protocol_fee_bps: u16) -> Result<()> {
+        require!(trade_fee_bps <= MAX_TRADE_FEE_BPS, ErrorCode::InvalidFeeConfig);
+        require!(protocol_fee_bps <= MAX_PROTOCOL_FEE_BPS, ErrorCode::InvalidFeeConfig);
+        require!(protocol_fee_bps <= trade_fee_bps, ErrorCode::InvalidFeeConfig);
+        Ok(())
+    }
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, PartialEq, InitSpace, Debug)]
 pub struct PoolStatus {
     pub is_active: bool,
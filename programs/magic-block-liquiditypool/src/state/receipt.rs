@@ -0,0 +1,127 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ErrorCode;
+
+// Lifecycle shared by deposit, withdraw and swap receipts:
+// Created -> Delegated -> AppliedInER -> Settled, or Created/Delegated -> Cancelled
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace, Debug)]
+pub enum ReceiptStatus {
+    #[default]
+    Created,
+    Delegated,
+    AppliedInER,
+    Settled,
+    Cancelled,
+}
+
+impl ReceiptStatus {
+    // The ER never touched the reserves for this receipt, so it can still be unwound
+    pub fn is_cancellable(&self) -> bool {
+        matches!(self, ReceiptStatus::Created | ReceiptStatus::Delegated)
+    }
+
+    // A plain commit-and-undelegate skips the mint/burn handler, so it is only allowed for a
+    // receipt the ER never applied and will no longer apply; it can then be cancelled.
+    // The ER still accepts a receipt at `expires_at` itself, hence strictly after.
+    pub fn require_undelegatable(&self, expires_at: i64, now: i64) -> Result<()> {
+        require!(*self == ReceiptStatus::Delegated, ErrorCode::InvalidReceiptStatus);
+        require!(now > expires_at, ErrorCode::ReceiptNotExpired);
+        Ok(())
+    }
+
+    // An applied receipt that is back on the base layer but still open means its mint/burn
+    // handler failed after the undelegation. Once it expired no handler is still on its way,
+    // so it is settled the same way directly.
+    pub fn require_retryable(&self, expires_at: i64, now: i64) -> Result<()> {
+        require!(*self == ReceiptStatus::AppliedInER, ErrorCode::InvalidReceiptStatus);
+        require!(now > expires_at, ErrorCode::ReceiptNotExpired);
+        Ok(())
+    }
+}
+
+// Open receipts of a pool, counted on the base layer where receipts are created and closed.
+// The pool itself is usually delegated and can't be written there, so the count lives in its
+// own PDA that is never delegated; reconciliation needs every one of these receipts.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct ReceiptCounter {
+    pub pool: Pubkey,
+    pub open_receipts: u64,
+    pub bump: u8,
+}
+
+impl ReceiptCounter {
+    // The first receipt of a pool creates the counter
+    pub fn open_receipt(&mut self, pool: Pubkey, bump: u8) -> Result<()> {
+        if self.pool == Pubkey::default() {
+            self.pool = pool;
+            self.bump = bump;
+        }
+        self.open_receipts = self.open_receipts.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn close_receipt(&mut self) -> Result<()> {
+        self.open_receipts = self.open_receipts.checked_sub(1).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    // A pool that never opened a receipt has no counter yet
+    pub fn load_open_receipts(info: &AccountInfo) -> Result<u64> {
+        if info.data_is_empty() {
+            return Ok(0);
+        }
+        require!(info.owner == &crate::ID, ErrorCode::InvalidReceipt);
+        let data = info.try_borrow_data()?;
+        Ok(ReceiptCounter::try_deserialize(&mut &data[..])?.open_receipts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undelegating_an_applied_receipt_is_rejected() {
+        assert!(ReceiptStatus::AppliedInER.require_undelegatable(100, 200).is_err());
+        assert!(ReceiptStatus::Settled.require_undelegatable(100, 200).is_err());
+        assert!(ReceiptStatus::Created.require_undelegatable(100, 200).is_err());
+    }
+
+    #[test]
+    fn undelegating_waits_for_expiry() {
+        assert!(ReceiptStatus::Delegated.require_undelegatable(100, 99).is_err());
+        assert!(ReceiptStatus::Delegated.require_undelegatable(100, 100).is_err());
+        assert!(ReceiptStatus::Delegated.require_undelegatable(100, 101).is_ok());
+        assert!(ReceiptStatus::Delegated.require_undelegatable(100, 200).is_ok());
+    }
+
+    #[test]
+    fn only_applied_receipts_are_retried() {
+        assert!(ReceiptStatus::AppliedInER.require_retryable(100, 200).is_ok());
+        assert!(ReceiptStatus::Created.require_retryable(100, 200).is_err());
+        assert!(ReceiptStatus::Delegated.require_retryable(100, 200).is_err());
+        assert!(ReceiptStatus::Settled.require_retryable(100, 200).is_err());
+        assert!(ReceiptStatus::Cancelled.require_retryable(100, 200).is_err());
+    }
+
+    #[test]
+    fn retrying_waits_for_expiry() {
+        assert!(ReceiptStatus::AppliedInER.require_retryable(100, 100).is_err());
+        assert!(ReceiptStatus::AppliedInER.require_retryable(100, 101).is_ok());
+    }
+
+    #[test]
+    fn counter_tracks_open_receipts() {
+        let pool = Pubkey::new_unique();
+        let mut counter = ReceiptCounter { pool: Pubkey::default(), open_receipts: 0, bump: 0 };
+
+        counter.open_receipt(pool, 254).unwrap();
+        counter.open_receipt(pool, 254).unwrap();
+        assert_eq!((counter.pool, counter.open_receipts, counter.bump), (pool, 2, 254));
+
+        counter.close_receipt().unwrap();
+        counter.close_receipt().unwrap();
+        assert!(counter.close_receipt().is_err());
+    }
+}
@@ -26,7 +26,6 @@ describe("magic-block-liquiditypool", () => {
   );
 
   const params = {
-    poolId: new anchor.BN(1),
     name: "SOL-USDC",
     maxAumUsd: new anchor.BN(1000000),
     metadataTitle: "SOL-USDC LP Token",
@@ -38,6 +37,9 @@ describe("magic-block-liquiditypool", () => {
   };
 
   let transferAuthorityAccount: PublicKey;
+  let factoryAccount: PublicKey;
+  let poolMappingAccount: PublicKey;
+  let poolRegistryPageAccount: PublicKey;
   let mintA: PublicKey;
   let mintB: PublicKey;
   let lpMint: PublicKey;
@@ -48,12 +50,19 @@ describe("magic-block-liquiditypool", () => {
   let metadataAccount: PublicKey;
 
   let liquidityProviderAccount: PublicKey;
+  let lpPositionAccount: PublicKey;
+  let receiptCounterAccount: PublicKey;
   let providerTokenAccountA: PublicKey;
   let providerTokenAccountB: PublicKey;
   let providerLpTokenAccount: PublicKey;
 
   let depositReceptAccount: PublicKey;
   let withdrawReceptAccount: PublicKey;
+  let withdrawLpEscrowAccount: PublicKey;
+  const depositReceiptId = new anchor.BN(Date.now());
+  const withdrawReceiptId = new anchor.BN(Date.now());
+  const swapReceiptId = new anchor.BN(Date.now());
+  let swapReceptAccount: PublicKey;
 
   console.log(provider.wallet.publicKey);
 
@@ -89,13 +98,23 @@ describe("magic-block-liquiditypool", () => {
       6
     );
 
-    [lpMint] = PublicKey.findProgramAddressSync(
-      [Buffer.from("lp_token_mint")],
+    // Pools are keyed by canonically ordered mints (mint_a < mint_b)
+    if (Buffer.compare(mintA.toBuffer(), mintB.toBuffer()) > 0) {
+      [mintA, mintB] = [mintB, mintA];
+    }
+
+    [factoryAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("factory")],
       program.programId
     );
 
-    [lpTokenAccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("lp_token_account")],
+    [poolMappingAccount] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("pool_mapping"),
+        mintA.toBuffer(),
+        mintB.toBuffer(),
+        new anchor.BN(params.tradeFees).toArrayLike(Buffer, "le", 2),
+      ],
       program.programId
     );
 
@@ -104,13 +123,23 @@ describe("magic-block-liquiditypool", () => {
       program.programId
     );
 
+    [lpMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lp_token_mint"), poolAccount.toBuffer()],
+      program.programId
+    );
+
+    [lpTokenAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lp_token_account"), poolAccount.toBuffer()],
+      program.programId
+    );
+
     [tokenVaultAaccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("token_account_a"), mintA.toBuffer()],
+      [Buffer.from("token_account_a"), poolAccount.toBuffer()],
       program.programId
     );
 
     [tokenVaultBaccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("token_account_b"), mintB.toBuffer()],
+      [Buffer.from("token_account_b"), poolAccount.toBuffer()],
       program.programId
     );
 
@@ -128,6 +157,16 @@ describe("magic-block-liquiditypool", () => {
       program.programId
     );
 
+    [lpPositionAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lp_position"), poolAccount.toBuffer(), provider.wallet.publicKey.toBuffer()],
+      program.programId
+    );
+
+    [receiptCounterAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt_counter"), poolAccount.toBuffer()],
+      program.programId
+    );
+
     let providerTokenAccountAaddress = await getOrCreateAssociatedTokenAccount(
       provider.connection,
       provider.wallet.payer,
@@ -164,12 +203,37 @@ describe("magic-block-liquiditypool", () => {
     );
 
     [depositReceptAccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("deposit_recept"), provider.wallet.publicKey.toBuffer()],
+      [
+        Buffer.from("deposit_recept"),
+        poolAccount.toBuffer(),
+        provider.wallet.publicKey.toBuffer(),
+        depositReceiptId.toArrayLike(Buffer, "le", 8),
+      ],
       program.programId
     );
 
     [withdrawReceptAccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("withdraw_recept"), provider.wallet.publicKey.toBuffer()],
+      [
+        Buffer.from("withdraw_recept"),
+        poolAccount.toBuffer(),
+        provider.wallet.publicKey.toBuffer(),
+        withdrawReceiptId.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+
+    [withdrawLpEscrowAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("withdraw_lp_escrow"), withdrawReceptAccount.toBuffer()],
+      program.programId
+    );
+
+    [swapReceptAccount] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("swap_recept"),
+        poolAccount.toBuffer(),
+        provider.wallet.publicKey.toBuffer(),
+        swapReceiptId.toArrayLike(Buffer, "le", 8),
+      ],
       program.programId
     );
 
@@ -185,15 +249,50 @@ describe("magic-block-liquiditypool", () => {
     console.log("Your transaction signature", tx);
   });
 
+  it("Initialize Factory", async () => {
+    const existing = await provider.connection.getAccountInfo(factoryAccount);
+    if (existing) {
+      console.log(`Factory already initialized: ${factoryAccount}`);
+      return;
+    }
+
+    // Only the program's upgrade authority may create the factory
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+
+    const tx = await program.methods.processInitializeFactory().accountsPartial({
+      authority: provider.wallet.publicKey,
+      factory: factoryAccount,
+      program: program.programId,
+      programData,
+      systemProgram: SystemProgram.programId,
+    }).signers([provider.wallet.payer]).rpc();
+
+    console.log(`Transaction Signature: ${tx}`);
+  });
+
   it("Initialize Liquidity Pool", async () => {
+    const factoryData = await program.account.factory.fetch(factoryAccount);
+    const registryPage = factoryData.poolCount.divn(32);
+
+    [poolRegistryPageAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("pool_registry"), registryPage.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
     const tx = await program.methods.processInitializePool(params).accountsPartial({
       admin: provider.wallet.publicKey,
       transferAuthority: transferAuthorityAccount,
+      factory: factoryAccount,
       mintA: mintA,
       mintB: mintB,
+      pool: poolAccount,
+      poolMapping: poolMappingAccount,
+      poolRegistryPage: poolRegistryPageAccount,
       lpMint: lpMint,
       lpTokenAccount: lpTokenAccount,
-      pool: poolAccount,
       tokenVaultA: tokenVaultAaccount,
       tokenVaultB: tokenVaultBaccount,
       systemProgram: SystemProgram.programId,
@@ -248,6 +347,44 @@ describe("magic-block-liquiditypool", () => {
     console.log(`Delegated Signature: ${signature}`);
   });
 
+  it("Pause and Resume Pool on ER", async () => {
+    const tx = new web3.Transaction().add(
+      await program.methods.processPausePool().accountsPartial({
+        admin: provider.wallet.publicKey,
+        pool: poolAccount,
+      }).instruction(),
+      await program.methods.processResumePool().accountsPartial({
+        admin: provider.wallet.publicKey,
+        pool: poolAccount,
+      }).instruction()
+    );
+
+    const signature = await sendMagicTransaction(
+      routerConnection,
+      tx,
+      [provider.wallet.payer]
+    );
+
+    console.log(`Paused and Resumed Pool: ${signature}`);
+  });
+
+  it("Commit Pool", async () => {
+    const tx = await program.methods.processCommitPool().accountsPartial({
+      admin: provider.wallet.publicKey,
+      pool: poolAccount,
+      magicContext: MAGIC_CONTEXT_ID,
+      magicProgram: MAGIC_PROGRAM_ID,
+    }).transaction();
+
+    const signature = await sendMagicTransaction(
+      routerConnection,
+      tx,
+      [provider.wallet.payer]
+    );
+
+    console.log(`Committed Pool: ${signature}`);
+  });
+
   it("Initialize Liquidity Provider", async () => {
     const tx = await program.methods.processInitializeLiquidityProvider().accountsPartial({
       provider: provider.wallet.publicKey,
@@ -279,17 +416,52 @@ describe("magic-block-liquiditypool", () => {
     console.log(`Delegated Signature: ${signature}`);
   });
 
+  it("Initialize LP Position", async () => {
+    const tx = await program.methods.processInitializeLpPosition().accountsPartial({
+      provider: provider.wallet.publicKey,
+      pool: poolAccount,
+      lpPosition: lpPositionAccount,
+      systemProgram: SystemProgram.programId,
+    }).signers([provider.wallet.payer]).rpc();
+
+    console.log(`Transaction Signature: ${tx}`);
+  });
+
+  it("Delegate LP Position", async () => {
+    let validatorKey = await getClosestValidator(routerConnection);
+    let commitFrequency = 30000;
+
+    const tx = await program.methods.processDelegateLpPosition(commitFrequency, validatorKey).accountsPartial({
+      provider: provider.wallet.publicKey,
+      lpPosition: lpPositionAccount
+    }).transaction();
+
+    const signature = await sendMagicTransaction(
+      routerConnection,
+      tx,
+      [provider.wallet.payer]
+    );
+
+    await sleepWithAnimation(10);
+
+    console.log(`Delegated LP Position!`);
+    console.log(`Delegated Signature: ${signature}`);
+  });
+
   it("Add Liquidity OnChain", async () => {
 
     let depositLiquidityParams = {
-      amountA: new anchor.BN(100),
-      amountB: new anchor.BN(100),
+      mode: { balanced: {} },
+      amountA: new anchor.BN(100_000),
+      amountB: new anchor.BN(100_000),
       minLpTokens: new anchor.BN(100),
-      pool: poolAccount,
+      receiptId: depositReceiptId,
+      validUntil: new anchor.BN(Math.floor(Date.now() / 1000) + 600),
     }
 
     const tx = await program.methods.processDepositAddLiquidityOnChain(depositLiquidityParams).accountsPartial({
       provider: provider.wallet.publicKey,
+      pool: poolAccount,
       mintA: mintA,
       mintB: mintB,
       transferAuthority: transferAuthorityAccount,
@@ -300,6 +472,7 @@ describe("magic-block-liquiditypool", () => {
       providerTokenBAta: providerTokenAccountB,
       providerTokenLpAta: providerLpTokenAccount,
       depositRecept: depositReceptAccount,
+      receiptCounter: receiptCounterAccount,
       tokenProgram: TOKEN_PROGRAM_ID,
       systemProgram: SystemProgram.programId,
     }).signers([provider.wallet.payer]).rpc();
@@ -331,6 +504,7 @@ describe("magic-block-liquiditypool", () => {
     const tx = await program.methods.processAddLiquidityEr().accountsPartial({
       provider: provider.wallet.publicKey,
       liquidityProvider: liquidityProviderAccount,
+      lpPosition: lpPositionAccount,
       pool: poolAccount,
       depositReceipt: depositReceptAccount,
       systemProgram: SystemProgram.programId
@@ -376,9 +550,16 @@ describe("magic-block-liquiditypool", () => {
       provider: provider.wallet.publicKey,
       pool: poolAccount,
       liquidityProvider: liquidityProviderAccount,
+      lpPosition: lpPositionAccount,
       depositRecept: depositReceptAccount,
+      receiptCounter: receiptCounterAccount,
       transferAuthority: transferAuthorityAccount,
       lpMint: lpMint,
+      lpTokenAccount: lpTokenAccount,
+      tokenVaultA: tokenVaultAaccount,
+      tokenVaultB: tokenVaultBaccount,
+      providerTokenAAta: providerTokenAccountA,
+      providerTokenBAta: providerTokenAccountB,
       providerLpAta: providerLpTokenAccount,
       tokenProgram: TOKEN_PROGRAM_ID,
       systemProgram: SystemProgram.programId,
@@ -399,14 +580,17 @@ describe("magic-block-liquiditypool", () => {
 
   it("Process Remove Liquidity OnChain", async () => {
     let removeLiquidityParams = {
-      lpTokensToBurn: new anchor.BN(5),
+      mode: { balanced: {} },
+      lpTokensToBurn: new anchor.BN(50),
       minAmountA: new anchor.BN(30),
       minAmountB: new anchor.BN(30),
-      pool: poolAccount
+      receiptId: withdrawReceiptId,
+      validUntil: new anchor.BN(Math.floor(Date.now() / 1000) + 600),
     };
 
     let tx = await program.methods.processRemoveLiquidityOnChain(removeLiquidityParams).accountsPartial({
       provider: provider.wallet.publicKey,
+      pool: poolAccount,
       mintA: mintA,
       mintB: mintB,
       transferAuthority: transferAuthorityAccount,
@@ -417,6 +601,8 @@ describe("magic-block-liquiditypool", () => {
       providerTokenBAta: providerTokenAccountB,
       providerTokenLpAta: providerLpTokenAccount,
       withdrawRecept: withdrawReceptAccount,
+      receiptCounter: receiptCounterAccount,
+      withdrawLpEscrow: withdrawLpEscrowAccount,
       tokenProgram: TOKEN_PROGRAM_ID,
       systemProgram: SystemProgram.programId
     }).signers([provider.wallet.payer]).rpc();
@@ -447,15 +633,17 @@ describe("magic-block-liquiditypool", () => {
   it("Process Remove Liquidity ER", async () => {
     let removeLiquidityParams = {
       user: provider.wallet.publicKey,
-      lpTokens: new anchor.BN(30),
-      minAmountA: new anchor.BN(30),
-      minAmountB: new anchor.BN(30),
+      lpTokens: new anchor.BN(50),
+      minAmountA: new anchor.BN(1),
+      minAmountB: new anchor.BN(1),
     };
 
     const tx = await program.methods.processRemoveLiquidityEr(removeLiquidityParams).accountsPartial({
       provider: provider.wallet.publicKey,
       liquidityProvider: liquidityProviderAccount,
+      lpPosition: lpPositionAccount,
       pool: poolAccount,
+      withdrawRecept: withdrawReceptAccount,
       systemProgram: SystemProgram.programId
     }).transaction();
 
@@ -475,9 +663,15 @@ describe("magic-block-liquiditypool", () => {
       provider: provider.wallet.publicKey,
       pool: poolAccount,
       liquidityProvider: liquidityProviderAccount,
+      lpPosition: lpPositionAccount,
       withdrawRecept: withdrawReceptAccount,
+      receiptCounter: receiptCounterAccount,
       lpMint: lpMint,
-      providerLpAta: providerLpTokenAccount,
+      withdrawLpEscrow: withdrawLpEscrowAccount,
+      tokenVaultA: tokenVaultAaccount,
+      tokenVaultB: tokenVaultBaccount,
+      providerTokenAAta: providerTokenAccountA,
+      providerTokenBAta: providerTokenAccountB,
       tokenProgram: TOKEN_PROGRAM_ID,
       magicContext: MAGIC_CONTEXT_ID,
       magicProgram: MAGIC_PROGRAM_ID
@@ -493,6 +687,97 @@ describe("magic-block-liquiditypool", () => {
     console.log(`Committed and Burned LP Tokens!`);
     console.log(`Transaction Signature: ${signature}`);
   })
+
+  it("Swap OnChain", async () => {
+    let swapParams = {
+      aToB: true,
+      mode: { exactIn: { amountIn: new anchor.BN(10), minAmountOut: new anchor.BN(1) } },
+      validUntil: new anchor.BN(Math.floor(Date.now() / 1000) + 600),
+      receiptId: swapReceiptId,
+    };
+
+    const tx = await program.methods.processSwapOnChain(swapParams).accountsPartial({
+      user: provider.wallet.publicKey,
+      pool: poolAccount,
+      mintA: mintA,
+      mintB: mintB,
+      transferAuthority: transferAuthorityAccount,
+      tokenVaultA: tokenVaultAaccount,
+      tokenVaultB: tokenVaultBaccount,
+      userTokenAAta: providerTokenAccountA,
+      userTokenBAta: providerTokenAccountB,
+      swapRecept: swapReceptAccount,
+      receiptCounter: receiptCounterAccount,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    }).signers([provider.wallet.payer]).rpc();
+
+    console.log(`Swap input escrowed: ${tx}`);
+  });
+
+  it("Delegate Swap Receipt", async () => {
+    let validatorKey = await getClosestValidator(routerConnection);
+    let commitFrequency = 30000;
+
+    const tx = await program.methods.processDelegateSwapReceipt(commitFrequency, validatorKey).accountsPartial({
+      user: provider.wallet.publicKey,
+      swapRecept: swapReceptAccount,
+    }).transaction();
+
+    const signature = await sendMagicTransaction(
+      routerConnection,
+      tx,
+      [provider.wallet.payer]
+    );
+
+    await sleepWithAnimation(10);
+    console.log(`Transaction Signature: ${signature}`);
+  });
+
+  it("Swap ER", async () => {
+    const tx = await program.methods.processSwapEr().accountsPartial({
+      user: provider.wallet.publicKey,
+      pool: poolAccount,
+      swapRecept: swapReceptAccount,
+    }).transaction();
+
+    const signature = await sendMagicTransaction(
+      routerConnection,
+      tx,
+      [provider.wallet.payer]
+    );
+
+    console.log(`Swapped on ER: ${signature}`);
+  });
+
+  it("Commit and Settle Swap", async () => {
+    const tx = await program.methods.processCommitAndSettleSwap().accountsPartial({
+      user: provider.wallet.publicKey,
+      swapRecept: swapReceptAccount,
+      receiptCounter: receiptCounterAccount,
+      pool: poolAccount,
+      transferAuthority: transferAuthorityAccount,
+      mintA: mintA,
+      mintB: mintB,
+      tokenVaultA: tokenVaultAaccount,
+      tokenVaultB: tokenVaultBaccount,
+      userTokenAAta: providerTokenAccountA,
+      userTokenBAta: providerTokenAccountB,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      magicContext: MAGIC_CONTEXT_ID,
+      magicProgram: MAGIC_PROGRAM_ID,
+    }).transaction();
+
+    const signature = await sendMagicTransaction(
+      routerConnection,
+      tx,
+      [provider.wallet.payer]
+    );
+
+    await sleepWithAnimation(10);
+    console.log(`Committed and Settled Swap!`);
+    console.log(`Transaction Signature: ${signature}`);
+  });
   
 });
 